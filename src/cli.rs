@@ -1,70 +1,62 @@
 pub mod args;
-use args::BuoArgs;
+pub mod bookmarks;
+pub mod cache;
+pub mod config;
+pub mod dupes;
+pub mod find;
+pub mod index;
+pub mod meta;
+pub mod output;
+pub mod search;
+pub mod visit;
+#[cfg(target_os = "linux")]
+pub mod watch;
+
+use args::{BuoArgs, BuoCmd};
 use clap::Clap;
+use output::EXIT_SUCCESS;
 
-use crate::{
-    prelude::*,
-    util::{json_out::ExportedJson, media::dispatch_meta_fn},
-};
+use crate::{prelude::*, util::config::BuoConfig};
 
 pub fn fetch_cli_args() -> Result<BuoArgs> {
-    let args = BuoArgs::parse();
-    if args.target_files.is_empty() {
-        bail!("No target files provided!")
-    } else {
-        Ok(args)
-    }
-}
-
-fn print_cli_output<Meta>(meta: ExportedJson<Meta>, json: bool, prettify: bool) -> Result<()>
-where
-    Meta: Serialize + std::fmt::Display,
-{
-    let formatted_output = if prettify {
-        meta.as_pretty_json()?
-    } else if json {
-        meta.as_json()?
-    } else {
-        meta.to_string()
-    };
-    println!("{}", formatted_output);
-    Ok(())
+    Ok(BuoArgs::parse())
 }
 
+/// Runs the requested subcommand and returns the process exit code
 pub fn dispatch_from_cli(
     BuoArgs {
-        json,
-        prettify,
-        target_files,
+        config: config_path,
+        no_ignore,
+        cmd,
     }: BuoArgs,
-) -> Result<()> {
-    for target_file in target_files {
-        // all directories get same treatment, dynamic dispatch not needed
-        if target_file.is_dir() {
-            use super::util::dirs::*;
+) -> Result<i32> {
+    let mut config = BuoConfig::load(config_path.as_deref())?;
+    if no_ignore {
+        config.disable_ignores();
+    }
 
-            let dir_meta = get_dir_meta(&target_file)?;
-            let wrapped_meta: ExportedJson<_> = dir_meta.into();
-            print_cli_output(wrapped_meta, json, prettify)?;
-        } else if let Some(dispatcher) = dispatch_meta_fn(&target_file) {
-            // FileExtCallback found, dynamically dispatching
-            let file_meta = dispatcher.try_get_meta(&target_file)?;
-            match file_meta {
-                Some(meta) => {
-                    let wrapped_meta: ExportedJson<_> = meta.into();
-                    print_cli_output(wrapped_meta, json, prettify)?;
-                }
-                None => println!("No metadata for {}", target_file.display()),
-            }
-        } else {
-            // No filetype associated callback found
-            let file_type = target_file
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("unknown");
-            println!("Filetype not supported: {}", file_type);
+    match cmd {
+        BuoCmd::Meta(meta_args) => meta::dispatch_meta(meta_args, &config),
+        BuoCmd::Index(index_args) => {
+            index::dispatch_index(index_args, &config).map(|_| EXIT_SUCCESS)
+        }
+        #[cfg(target_os = "linux")]
+        BuoCmd::Watch(watch_args) => {
+            watch::dispatch_watch(watch_args, &config).map(|_| EXIT_SUCCESS)
+        }
+        #[cfg(not(target_os = "linux"))]
+        BuoCmd::Watch(_) => bail!("watch relies on inotify and is only available on Linux"),
+        BuoCmd::Search(search_args) => search::dispatch_search(search_args, &config),
+        BuoCmd::Find(find_args) => find::dispatch_find(find_args, &config),
+        BuoCmd::Visit(visit_args) => {
+            visit::dispatch_visit(visit_args, &config).map(|_| EXIT_SUCCESS)
+        }
+        BuoCmd::Dupes(dupes_args) => dupes::dispatch_dupes(dupes_args, &config),
+        BuoCmd::Bookmarks(bookmark_args) => bookmarks::dispatch_bookmarks(bookmark_args, &config),
+        BuoCmd::Cache(cache_args) => cache::dispatch_cache(cache_args, &config),
+        BuoCmd::Config(config_args) => {
+            config::dispatch_config(config_args, &config, config_path.as_deref())
+                .map(|_| EXIT_SUCCESS)
         }
     }
-
-    Ok(())
 }
@@ -1,15 +1,289 @@
+use crate::util::{
+    file_types::FileCategory, filter::FileFilter, template::Template, web::BookmarkSource,
+};
 use clap::{AppSettings, Clap};
 use std::path::PathBuf;
 
+const EXIT_CODES: &str = "EXIT CODES:
+    0   every target succeeded
+    1   buo itself failed
+    The following are combined when targets end up in several states:
+    4   some targets are unsupported file types
+    8   some targets had no metadata
+    16  some targets could not be parsed
+    32  some targets could not be read
+    130 the interactive finder was cancelled";
+
 #[derive(Clap)]
-#[clap(setting = AppSettings::ColoredHelp)]
+#[clap(setting = AppSettings::ColoredHelp, after_help = EXIT_CODES)]
 pub struct BuoArgs {
-    /// output serialized json
+    /// read settings from this file instead of ~/.config/buo/config.toml
     #[clap(short, long)]
+    pub config: Option<PathBuf>,
+    /// walk into everything, ignoring .gitignore, .buoignore and the config's ignore patterns
+    #[clap(long, global = true)]
+    pub no_ignore: bool,
+    #[clap(subcommand)]
+    pub cmd: BuoCmd,
+}
+
+#[derive(Clap)]
+pub enum BuoCmd {
+    /// print metadata for files and directories
+    Meta(MetaArgs),
+    /// add supported files under a directory to the metadata index
+    Index(IndexArgs),
+    /// keep the metadata index up to date as files change under watched directories
+    Watch(WatchArgs),
+    /// search the metadata index
+    Search(SearchArgs),
+    /// fuzzy find indexed files by path, title and author
+    Find(FindArgs),
+    /// record that indexed files were opened, ranking them higher in search and find
+    Visit(VisitArgs),
+    /// find files with identical contents and the space removing the copies would free
+    Dupes(DupesArgs),
+    /// list browser bookmarks
+    Bookmarks(BookmarkArgs),
+    /// manage the persistent cache
+    Cache(CacheArgs),
+    /// inspect the configuration
+    Config(ConfigArgs),
+}
+
+#[derive(Clap)]
+pub struct OutputArgs {
+    /// output newline delimited json, one record per target
+    #[clap(short, long, alias = "ndjson")]
     pub json: bool,
+    /// output a single json array holding every record
+    #[clap(short, long)]
+    pub array: bool,
     /// prettify json output
     #[clap(short, long)]
     pub prettify: bool,
+    /// output comma separated values
+    #[clap(long, conflicts_with = "tsv")]
+    pub csv: bool,
+    /// output tab separated values
+    #[clap(long)]
+    pub tsv: bool,
+    /// omit the header row from csv and tsv output
+    #[clap(long)]
+    pub no_header: bool,
+    /// render each record with a template, e.g. '{file_name}\t[{author} - ]{title}'
+    #[clap(short, long)]
+    pub format: Option<Template>,
+}
+
+#[derive(Clap)]
+pub struct FilterArgs {
+    /// only include these categories: audio, video, text, img, dev
+    #[clap(short, long = "type", require_delimiter = true)]
+    pub types: Vec<FileCategory>,
+    /// only include these file extensions
+    #[clap(short, long = "ext", require_delimiter = true)]
+    pub exts: Vec<String>,
+    /// exclude these categories
+    #[clap(long = "exclude-type", require_delimiter = true)]
+    pub exclude_types: Vec<FileCategory>,
+    /// exclude these file extensions
+    #[clap(long = "exclude-ext", require_delimiter = true)]
+    pub exclude_exts: Vec<String>,
+}
+
+impl From<FilterArgs> for FileFilter {
+    fn from(
+        FilterArgs {
+            types,
+            exts,
+            exclude_types,
+            exclude_exts,
+        }: FilterArgs,
+    ) -> Self {
+        // `.flac` and `flac` name the same extension, as `ext:` does in queries
+        let normalize = |exts: Vec<String>| -> Vec<String> {
+            exts.into_iter()
+                .map(|ext| ext.trim_start_matches('.').to_owned())
+                .collect()
+        };
+
+        Self {
+            types,
+            exts: normalize(exts),
+            exclude_types,
+            exclude_exts: normalize(exclude_exts),
+        }
+    }
+}
+
+#[derive(Clap)]
+pub struct MetaArgs {
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+    /// print metadata for every supported file inside directory targets
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// maximum number of directories to descend into when recursive
+    #[clap(short = 'd', long, requires = "recursive")]
+    pub max_depth: Option<usize>,
+    /// number of files to analyze concurrently, output order is preserved
+    #[clap(long, default_value = "1")]
+    pub jobs: usize,
+    /// read target paths from stdin, same as passing `-` as a target
+    #[clap(long)]
+    pub stdin: bool,
+    /// target paths on stdin are separated by NUL instead of newlines
+    #[clap(short = '0', long)]
+    pub null: bool,
     #[clap(name = "target_file")]
     pub target_files: Vec<PathBuf>,
 }
+
+#[derive(Clap)]
+pub struct IndexArgs {
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+    /// maximum number of files to add
+    #[clap(short, long)]
+    pub limit: Option<usize>,
+    pub root: PathBuf,
+}
+
+#[derive(Clap)]
+pub struct WatchArgs {
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+    /// directories to watch, defaults to the `[watch] roots` of the config
+    pub roots: Vec<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct SearchArgs {
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    /// words matched against file name, title, author and extras, and field clauses like
+    /// type:audio author:"Daft Punk" duration>3m date<2010 lang:rust size>1GB loc>=100.
+    /// End a word with * to match prefixes, quote words to match a phrase, and prefix a
+    /// clause with - to negate it (after a `--` separator)
+    #[clap(required = true)]
+    pub terms: Vec<String>,
+}
+
+#[derive(Clap)]
+pub struct FindArgs {
+    /// pick from a live list with a metadata preview, tab selects several entries
+    #[clap(short, long)]
+    pub interactive: bool,
+    /// separate printed paths with NUL instead of newlines
+    #[clap(short = '0', long)]
+    pub null: bool,
+    /// initial fuzzy query, every indexed file is listed when empty
+    pub query: Option<String>,
+}
+
+#[derive(Clap)]
+pub struct VisitArgs {
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+fn parse_size(size: &str) -> Result<u64, String> {
+    byte_unit::Byte::from_str(size)
+        .map(|bytes| bytes.get_bytes() as u64)
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Clap)]
+pub struct DupesArgs {
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    /// skip files smaller than this, e.g. 4KB or 1MiB
+    #[clap(short, long, default_value = "1", parse(try_from_str = parse_size))]
+    pub min_size: u64,
+    /// directories searched for duplicates, files may be passed too
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct BookmarkArgs {
+    #[clap(flatten)]
+    pub output: OutputArgs,
+    /// only read bookmarks from this browser
+    #[clap(short, long)]
+    pub browser: Option<BookmarkSource>,
+}
+
+#[derive(Clap)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    pub cmd: CacheCmd,
+}
+
+#[derive(Clap)]
+pub enum CacheCmd {
+    /// print the location of the persistent cache
+    Path,
+    /// reinitialize the persistent cache, dropping all entries
+    Init,
+    /// print the number of entries, eviction policy and hit rate
+    Stats,
+    /// print the metadata of every cached file
+    List(CacheListArgs),
+    /// drop the entries of files that no longer exist
+    Prune(CachePruneArgs),
+    /// drop every entry, keeping capacity and statistics
+    Clear,
+    /// write every entry as json
+    Export(CacheExportArgs),
+    /// replace the cache with entries exported as json
+    Import(CacheImportArgs),
+}
+
+#[derive(Clap)]
+pub struct CacheListArgs {
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Clap)]
+pub struct CachePruneArgs {
+    /// also drop the entries of files modified since they were cached
+    #[clap(short, long)]
+    pub stale: bool,
+}
+
+#[derive(Clap)]
+pub struct CacheExportArgs {
+    /// prettify json output
+    #[clap(short, long)]
+    pub prettify: bool,
+    /// write to this file instead of stdout
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct CacheImportArgs {
+    /// keep the current entries, imported ones replace those for the same files
+    #[clap(short, long)]
+    pub merge: bool,
+    /// read from this file instead of stdin
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct ConfigArgs {
+    #[clap(subcommand)]
+    pub cmd: ConfigCmd,
+}
+
+#[derive(Clap)]
+pub enum ConfigCmd {
+    /// print the location of the config file
+    Path,
+    /// print the effective configuration, defaults included
+    Show,
+}
@@ -0,0 +1,37 @@
+use super::{
+    args::BookmarkArgs,
+    output::{apply_output_config, Printer},
+};
+use crate::{prelude::*, util::config::BuoConfig};
+
+pub fn dispatch_bookmarks(
+    BookmarkArgs {
+        mut output,
+        browser,
+    }: BookmarkArgs,
+    config: &BuoConfig,
+) -> Result<i32> {
+    apply_output_config(&mut output, &config.output)?;
+    let bookmark_config = &config.bookmarks;
+
+    let mut printer = Printer::new(&output);
+    if let Some(source) = browser {
+        for bookmark in source.fetch_bookmarks(bookmark_config.profile_for(source))? {
+            printer.print(bookmark.into())?;
+        }
+        return printer.finish();
+    }
+
+    // without an explicit browser, read every source and skip the missing ones
+    for &source in &bookmark_config.sources {
+        match source.fetch_bookmarks(bookmark_config.profile_for(source)) {
+            Ok(bookmarks) => {
+                for bookmark in bookmarks {
+                    printer.print(bookmark.into())?;
+                }
+            }
+            Err(e) => eprintln!("Skipping {} bookmarks: {}", source.as_ref(), e),
+        }
+    }
+    printer.finish()
+}
@@ -0,0 +1,189 @@
+use super::{
+    args::{CacheArgs, CacheCmd, CacheExportArgs, CacheImportArgs, CacheListArgs, CachePruneArgs},
+    output::{apply_output_config, Printer, EXIT_SUCCESS},
+};
+use crate::{
+    prelude::*,
+    util::{
+        cache::{commit_cache_to_path, retrieve_configured_cache, update_cache, PersistentCache},
+        config::{BuoConfig, CacheConfig},
+    },
+};
+use std::{
+    fs::{read_to_string, write},
+    io::{stdin, Read},
+    path::Path,
+};
+
+fn print_stats(cache: &PersistentCache) {
+    let stats = cache.stats();
+    let (hits, misses) = (stats.hits(), stats.misses());
+    let lookups = hits + misses;
+    let hit_rate = if lookups == 0 {
+        0.0
+    } else {
+        hits as f64 * 100.0 / lookups as f64
+    };
+
+    println!("entries:    {} of {}", cache.len(), cache.capacity());
+    println!("eviction:   {}", cache.policy());
+    println!("hits:       {} ({:.1}%)", hits, hit_rate);
+    println!("misses:     {}", misses);
+    println!("insertions: {}", stats.insertions());
+    println!("evictions:  {}", stats.evictions());
+}
+
+fn list(
+    CacheListArgs { mut output }: CacheListArgs,
+    cache_path: &Path,
+    config: &BuoConfig,
+) -> Result<i32> {
+    apply_output_config(&mut output, &config.output)?;
+    let cache = retrieve_configured_cache(cache_path, &config.cache)?;
+
+    let mut printer = Printer::new(&output);
+    for (_, meta) in cache.entries() {
+        printer.print(meta.clone().into())?;
+    }
+    printer.finish()
+}
+
+fn prune(
+    CachePruneArgs { stale }: CachePruneArgs,
+    cache_path: &Path,
+    config: &CacheConfig,
+) -> Result<()> {
+    let pruned = update_cache(cache_path, config, |cache| {
+        if stale {
+            return cache.prune_stale();
+        }
+        let before = cache.len();
+        cache.retain(|path, _| !path.exists());
+        before - cache.len()
+    })?;
+    println!("Pruned {} entries from {}", pruned, cache_path.display());
+    Ok(())
+}
+
+fn export(
+    CacheExportArgs { prettify, file }: CacheExportArgs,
+    cache_path: &Path,
+    config: &CacheConfig,
+) -> Result<()> {
+    let cache = retrieve_configured_cache(cache_path, config)?;
+    let json = cache.to_json(prettify)?;
+
+    match file {
+        Some(file) => {
+            write(&file, json)?;
+            println!("Exported {} entries to {}", cache.len(), file.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn import(
+    CacheImportArgs { merge, file }: CacheImportArgs,
+    cache_path: &Path,
+    config: &BuoConfig,
+) -> Result<()> {
+    let json = match file {
+        Some(file) => read_to_string(&file)
+            .map_err(|e| anyhow!("Unable to read {}: {}", file.display(), e))?,
+        None => {
+            let mut json = String::new();
+            stdin().read_to_string(&mut json)?;
+            json
+        }
+    };
+
+    let imported = if merge {
+        update_cache(cache_path, &config.cache, |cache| cache.import_json(&json))??
+    } else {
+        let mut cache = config.cache.new_cache();
+        let imported = cache.import_json(&json)?;
+        commit_cache_to_path(cache_path, cache)?;
+        imported
+    };
+    println!(
+        "Imported {} entries into {}",
+        imported,
+        cache_path.display()
+    );
+    Ok(())
+}
+
+pub fn dispatch_cache(CacheArgs { cmd }: CacheArgs, config: &BuoConfig) -> Result<i32> {
+    let cache_path = config.cache_path()?;
+
+    match cmd {
+        CacheCmd::Path => println!("{}", cache_path.display()),
+        CacheCmd::Init => {
+            commit_cache_to_path(&cache_path, config.cache.new_cache())?;
+            println!("Initialized empty cache at {}", cache_path.display());
+        }
+        CacheCmd::Stats => print_stats(&retrieve_configured_cache(&cache_path, &config.cache)?),
+        CacheCmd::List(list_args) => return list(list_args, &cache_path, config),
+        CacheCmd::Prune(prune_args) => prune(prune_args, &cache_path, &config.cache)?,
+        CacheCmd::Clear => {
+            let cleared = update_cache(&cache_path, &config.cache, |cache| {
+                let cleared = cache.len();
+                cache.retain(|_, _| true);
+                cleared
+            })?;
+            println!("Cleared {} entries from {}", cleared, cache_path.display());
+        }
+        CacheCmd::Export(export_args) => export(export_args, &cache_path, &config.cache)?,
+        CacheCmd::Import(import_args) => import(import_args, &cache_path, config)?,
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+#[test]
+fn exports_and_imports_dated_entries() -> Result<()> {
+    use crate::util::{cache::retrieve_or_init_cache, media::meta::DateKind};
+    use chrono::{DateTime, Utc};
+    use clap::Clap;
+    use std::ffi::OsStr;
+
+    let tmp = tempfile::tempdir()?;
+    let song = tmp.path().join("song.mp3");
+    std::fs::write(&song, "buo")?;
+    let (source, target) = (tmp.path().join("source"), tmp.path().join("target"));
+    let json = tmp.path().join("cache.json");
+
+    let cache = |cache_path: &Path, args: &[&OsStr]| -> Result<i32> {
+        let config = BuoConfig {
+            cache: CacheConfig {
+                path: Some(cache_path.to_path_buf()),
+                ..CacheConfig::default()
+            },
+            ..BuoConfig::default()
+        };
+        let args = std::iter::once(OsStr::new("cache")).chain(args.iter().copied());
+        dispatch_cache(CacheArgs::try_parse_from(args)?, &config)
+    };
+
+    let released: DateTime<Utc> = "2021-03-14T15:09:26Z".parse()?;
+    update_cache(&source, &CacheConfig::default(), |cache| {
+        let meta = MediaMeta {
+            file_path: song.clone(),
+            date: Some(DateKind::Chrono(released)),
+            ..Default::default()
+        };
+        cache.insert(&song, meta)
+    })??;
+
+    cache(&source, &["export".as_ref(), json.as_os_str()])?;
+    cache(&target, &["import".as_ref(), json.as_os_str()])?;
+    assert_eq!(
+        cache(&target, &["list".as_ref(), "-j".as_ref()])?,
+        EXIT_SUCCESS
+    );
+
+    let mut imported = retrieve_or_init_cache(&target)?;
+    let date = imported.get(&song).and_then(|meta| meta.date.clone());
+    assert!(matches!(date, Some(DateKind::Chrono(date)) if date == released));
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use super::args::{ConfigArgs, ConfigCmd};
+use crate::{prelude::*, util::config::BuoConfig};
+use std::path::Path;
+
+pub fn dispatch_config(
+    ConfigArgs { cmd }: ConfigArgs,
+    config: &BuoConfig,
+    config_path: Option<&Path>,
+) -> Result<()> {
+    match cmd {
+        ConfigCmd::Path => match config_path {
+            Some(config_path) => println!("{}", config_path.display()),
+            None => println!("{}", BuoConfig::default_path()?.display()),
+        },
+        ConfigCmd::Show => print!("{}", config.to_toml()?),
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use super::{
+    args::DupesArgs,
+    output::{apply_output_config, Printer},
+};
+use crate::{
+    prelude::*,
+    util::{config::BuoConfig, dirs::human_readable_bytes, dupes::find_dupes, walk::collect_files},
+};
+
+pub fn dispatch_dupes(
+    DupesArgs {
+        mut output,
+        min_size,
+        paths,
+    }: DupesArgs,
+    config: &BuoConfig,
+) -> Result<i32> {
+    apply_output_config(&mut output, &config.output)?;
+    let ignorer = config.ignorer()?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(collect_files(&path, None, &ignorer)?);
+        } else if path.is_file() {
+            files.push(path);
+        } else {
+            bail!("{} is not a file or directory!", path.display());
+        }
+    }
+
+    let groups = find_dupes(files, min_size);
+    let reclaimable: u64 = groups.iter().map(|group| group.reclaimable).sum();
+    // stderr, so stdout stays one record per group for scripts
+    eprintln!(
+        "{} groups of duplicates, {} reclaimable",
+        groups.len(),
+        human_readable_bytes(reclaimable)
+    );
+
+    let mut printer = Printer::new(&output);
+    for group in groups {
+        printer.print(group.into())?;
+    }
+    printer.finish()
+}
@@ -0,0 +1,142 @@
+mod picker;
+
+use super::{args::FindArgs, output::EXIT_SUCCESS};
+use crate::{
+    prelude::*,
+    util::{
+        config::BuoConfig,
+        history::{frecencies, unix_now},
+        index::MetaIndex,
+        ranker::{features, text_match, Ranker, Sample, MAX_SKIPPED},
+    },
+};
+use picker::{fuzzy_matches, pick, Candidate, Picked};
+use std::{
+    collections::HashMap,
+    io::{stdout, Write},
+    path::PathBuf,
+};
+
+/// Exit code when the interactive picker is dismissed without a choice
+pub const EXIT_CANCELLED: i32 = 130;
+
+fn candidate_line(meta: &MediaMeta) -> String {
+    let path = meta.file_path.to_string_lossy();
+    match (meta.author.as_ref(), meta.title.as_ref()) {
+        (Some(author), Some(title)) => format!("{}  {} - {}", path, author, title),
+        (None, Some(title)) => format!("{}  {}", path, title),
+        _ => path.to_string(),
+    }
+}
+
+/// Chosen entries are accepted, the ones listed above the last choice were skipped
+fn feedback(
+    Picked { chosen, ranked }: &Picked,
+    entries: &[MediaMeta],
+    frecencies: &HashMap<PathBuf, f64>,
+    now: i64,
+) -> Vec<Sample> {
+    let last_chosen = match ranked.iter().rposition(|(index, _)| chosen.contains(index)) {
+        Some(last_chosen) => last_chosen,
+        // nothing chosen was among the ranked entries, so there is nothing to learn from
+        None => return Vec::new(),
+    };
+    let best = ranked
+        .iter()
+        .map(|&(_, score)| score as f64)
+        .fold(0.0, f64::max);
+
+    let sample = |&(index, score): &(usize, i64)| {
+        let meta = &entries[index];
+        let text_match = text_match(Some(score as f64), best);
+        let frecency = frecencies.get(&meta.file_path).copied().unwrap_or_default();
+        Sample {
+            features: features(meta, text_match, frecency, now),
+            accepted: chosen.contains(&index),
+        }
+    };
+
+    let (accepted, skipped): (Vec<_>, Vec<_>) = ranked[..=last_chosen]
+        .iter()
+        .map(sample)
+        .partition(|sample| sample.accepted);
+    accepted
+        .into_iter()
+        .chain(skipped.into_iter().take(MAX_SKIPPED))
+        .collect()
+}
+
+pub fn dispatch_find(
+    FindArgs {
+        interactive,
+        null,
+        query,
+    }: FindArgs,
+    config: &BuoConfig,
+) -> Result<i32> {
+    let mut index = MetaIndex::open(&config.index_path()?)?;
+    let mut entries = index.entries()?;
+    let frecencies = frecencies(&index, &config.history)?;
+    let ranker_path = config.ranker_path()?;
+    let mut ranker = match config.ranker.enabled {
+        true => Some(Ranker::load(&ranker_path)?),
+        false => None,
+    };
+
+    // fuzzy matches keep this order on equal scores, so likely picks come first.
+    // Priors are taken before anything is typed, so nothing is matched against yet.
+    let now = unix_now();
+    let prior = |meta: &MediaMeta| {
+        let frecency = frecencies.get(&meta.file_path).copied().unwrap_or_default();
+        match ranker {
+            Some(ref ranker) if ranker.is_trained() => {
+                ranker.predict(&features(meta, text_match(None, 0.0), frecency, now))
+            }
+            _ => frecency,
+        }
+    };
+    let mut priors: Vec<(f64, MediaMeta)> =
+        entries.drain(..).map(|meta| (prior(&meta), meta)).collect();
+    priors.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    entries.extend(priors.into_iter().map(|(_, meta)| meta));
+
+    let candidates: Vec<Candidate> = entries
+        .iter()
+        .map(|meta| Candidate {
+            line: candidate_line(meta),
+            preview: format!("path: {}\n{}", meta.file_path.display(), meta),
+        })
+        .collect();
+
+    let query = query.unwrap_or_default();
+    let picked = if interactive {
+        let picked = pick(&candidates, &query)?;
+        if picked.chosen.is_empty() {
+            return Ok(EXIT_CANCELLED);
+        }
+
+        if config.history.record {
+            for &index_pos in &picked.chosen {
+                index.record_visit(&entries[index_pos].file_path, now)?;
+            }
+        }
+        if let Some(ref mut ranker) = ranker {
+            ranker.learn(feedback(&picked, &entries, &frecencies, now));
+            ranker.save(&ranker_path)?;
+        }
+        picked.chosen
+    } else {
+        fuzzy_matches(&candidates, &query)
+            .into_iter()
+            .map(|(index, _, _)| index)
+            .collect()
+    };
+
+    let separator = if null { '\0' } else { '\n' };
+    let mut out = stdout();
+    for index in picked {
+        write!(out, "{}{}", entries[index].file_path.display(), separator)?;
+    }
+    out.flush()?;
+    Ok(EXIT_SUCCESS)
+}
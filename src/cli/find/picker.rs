@@ -0,0 +1,272 @@
+use crate::prelude::*;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{read, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::{
+    collections::BTreeSet,
+    io::{stderr, Stderr, Write},
+};
+
+/// Below this width the preview pane is dropped so paths stay readable
+const MIN_PREVIEW_WIDTH: u16 = 80;
+
+pub struct Candidate {
+    /// text shown in the list and matched against
+    pub line: String,
+    pub preview: String,
+}
+
+struct Match {
+    index: usize,
+    score: i64,
+    /// char positions of `line` that matched the query
+    positions: Vec<usize>,
+}
+
+/// Ranks candidates matching `query`, best first, ties keep their original order
+pub fn fuzzy_matches(candidates: &[Candidate], query: &str) -> Vec<(usize, i64, Vec<usize>)> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut matches: Vec<_> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| {
+            let (score, positions) = matcher.fuzzy_indices(&candidate.line, query)?;
+            Some((index, score, positions))
+        })
+        .collect();
+
+    matches.sort_by(|(i, a, _), (j, b, _)| b.cmp(a).then(i.cmp(j)));
+    matches
+}
+
+/// Puts the terminal back even when drawing fails halfway
+struct TerminalGuard(Stderr);
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        let mut out = stderr();
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Self(out))
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(self.0, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+struct Picker<'c> {
+    candidates: &'c [Candidate],
+    query: String,
+    matches: Vec<Match>,
+    cursor: usize,
+    scroll: usize,
+    selected: BTreeSet<usize>,
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+impl<'c> Picker<'c> {
+    fn rematch(&mut self) {
+        self.matches = fuzzy_matches(self.candidates, &self.query)
+            .into_iter()
+            .map(|(index, score, positions)| Match {
+                index,
+                score,
+                positions,
+            })
+            .collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.matches.get(self.cursor).map(|m| m.index)
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        if down && self.cursor + 1 < self.matches.len() {
+            self.cursor += 1;
+        } else if !down {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+    }
+
+    fn draw_line(&self, out: &mut impl Write, m: &Match, width: usize) -> Result<()> {
+        let line = &self.candidates[m.index].line;
+        for (pos, c) in line.chars().take(width).enumerate() {
+            if m.positions.contains(&pos) {
+                queue!(out, SetForegroundColor(Color::Green), Print(c), ResetColor)?;
+            } else {
+                queue!(out, Print(c))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (cols, rows) = size()?;
+        let list_height = rows.saturating_sub(1) as usize;
+        let list_width = if cols >= MIN_PREVIEW_WIDTH {
+            cols / 2
+        } else {
+            cols
+        };
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + list_height {
+            self.scroll = self.cursor + 1 - list_height;
+        }
+
+        queue!(out, Clear(ClearType::All))?;
+        for (row, m) in self
+            .matches
+            .iter()
+            .skip(self.scroll)
+            .take(list_height)
+            .enumerate()
+        {
+            let is_cursor = self.scroll + row == self.cursor;
+            let marker = match (is_cursor, self.selected.contains(&m.index)) {
+                (true, true) => ">*",
+                (true, false) => "> ",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+
+            queue!(out, MoveTo(0, row as u16), Print(marker))?;
+            if is_cursor {
+                queue!(out, SetAttribute(Attribute::Bold))?;
+            }
+            self.draw_line(out, m, list_width.saturating_sub(3) as usize)?;
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+
+        if list_width < cols {
+            let preview_width = (cols - list_width - 2) as usize;
+            let preview = self
+                .current()
+                .map(|index| self.candidates[index].preview.as_str())
+                .unwrap_or_default();
+            let lines = preview.lines().chain(std::iter::repeat(""));
+
+            for (row, line) in lines.take(list_height).enumerate() {
+                queue!(
+                    out,
+                    MoveTo(list_width, row as u16),
+                    Print("│ "),
+                    Print(truncate(line, preview_width))
+                )?;
+            }
+        }
+
+        let status = format!(
+            "  {}/{}{}",
+            self.matches.len(),
+            self.candidates.len(),
+            if self.selected.is_empty() {
+                String::new()
+            } else {
+                format!(" ({} selected)", self.selected.len())
+            }
+        );
+        queue!(
+            out,
+            MoveTo(0, rows.saturating_sub(1)),
+            Print("> "),
+            Print(truncate(&self.query, cols.saturating_sub(2) as usize)),
+            SetForegroundColor(Color::DarkGrey),
+            Print(status),
+            ResetColor
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// `Some` once the picker is done, holding the chosen candidates or nothing if cancelled
+    fn handle_key(&mut self, KeyEvent { code, modifiers }: KeyEvent) -> Option<Vec<usize>> {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Esc => return Some(Vec::new()),
+            KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return Some(Vec::new()),
+            KeyCode::Enter => {
+                if self.selected.is_empty() {
+                    return Some(self.current().into_iter().collect());
+                }
+                return Some(self.selected.iter().copied().collect());
+            }
+            KeyCode::Up => self.move_cursor(false),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => self.move_cursor(false),
+            KeyCode::Down => self.move_cursor(true),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => self.move_cursor(true),
+            KeyCode::Tab => {
+                if let Some(index) = self.current() {
+                    if !self.selected.remove(&index) {
+                        self.selected.insert(index);
+                    }
+                    self.move_cursor(true);
+                }
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.rematch();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.rematch();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.rematch();
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+pub struct Picked {
+    /// chosen candidates, empty when the picker was cancelled
+    pub chosen: Vec<usize>,
+    /// candidates and their match scores as listed when the choice was made
+    pub ranked: Vec<(usize, i64)>,
+}
+
+/// Lets the user pick candidates on the terminal, drawing on stderr so stdout stays free
+/// for the result. Tab toggles multiple selections, Enter accepts, Esc cancels.
+pub fn pick(candidates: &[Candidate], query: &str) -> Result<Picked> {
+    let mut picker = Picker {
+        candidates,
+        query: query.to_owned(),
+        matches: Vec::new(),
+        cursor: 0,
+        scroll: 0,
+        selected: BTreeSet::new(),
+    };
+    picker.rematch();
+
+    let mut guard = TerminalGuard::enter()?;
+    loop {
+        picker.draw(&mut guard.0)?;
+        if let Event::Key(key) = read()? {
+            if let Some(chosen) = picker.handle_key(key) {
+                let ranked = picker.matches.iter().map(|m| (m.index, m.score)).collect();
+                return Ok(Picked { chosen, ranked });
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::args::IndexArgs;
+use crate::{
+    prelude::*,
+    util::{
+        cache::get_entries_below,
+        config::{AnalyzerConfig, BuoConfig},
+        filter::FileFilter,
+        fingerprint::{diff_fingerprints, Fingerprint},
+        index::MetaIndex,
+        media::dispatch_meta_fn,
+        walk::Ignorer,
+    },
+};
+use std::{
+    fmt,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+};
+
+/// Decides which files below an indexed root are analyzed, shared by `index` and `watch`
+pub struct IndexRules {
+    analyzers: AnalyzerConfig,
+    filter: FileFilter,
+    pub ignorer: Ignorer,
+}
+
+impl IndexRules {
+    pub fn new(config: &BuoConfig, filter: FileFilter) -> Result<Self> {
+        Ok(Self {
+            analyzers: config.analyzers,
+            filter,
+            ignorer: config.ignorer()?,
+        })
+    }
+
+    /// Ignore rules aren't checked here, walks already skip ignored paths
+    pub fn allows(&self, path: &Path) -> bool {
+        path.is_file()
+            && dispatch_meta_fn(path).is_some()
+            && self.analyzers.allows_path(path)
+            && self.filter.matches(path)
+    }
+}
+
+/// What an index update did, with files that yielded no metadata counted apart
+#[derive(Debug, Default)]
+pub struct IndexSummary {
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+impl IndexSummary {
+    pub fn has_changes(&self) -> bool {
+        self.added + self.modified + self.removed + self.failed > 0
+    }
+}
+
+impl AddAssign for IndexSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.added += other.added;
+        self.modified += other.modified;
+        self.removed += other.removed;
+        self.unchanged += other.unchanged;
+        self.failed += other.failed;
+    }
+}
+
+impl fmt::Display for IndexSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} modified, {} removed, {} unchanged",
+            self.added, self.modified, self.removed, self.unchanged
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} without metadata", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Analyzes `stale` files into `analyzed`, or `failed` for those without metadata.
+/// Returns how many had metadata.
+fn analyze_stale(
+    stale: &[(PathBuf, Fingerprint)],
+    analyzed: &mut Vec<(MediaMeta, Fingerprint)>,
+    failed: &mut Vec<(PathBuf, Fingerprint)>,
+) -> usize {
+    let before = analyzed.len();
+    for (path, fingerprint) in stale {
+        // entries were validated by the walker, so a dispatcher always exists
+        let meta = match dispatch_meta_fn(path) {
+            Some(dispatcher) => dispatcher.try_get_meta(path),
+            None => Ok(None),
+        };
+
+        match meta {
+            Ok(Some(mut meta)) => {
+                meta.file_path = path.clone();
+                analyzed.push((meta, *fingerprint));
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+        failed.push((path.clone(), *fingerprint));
+    }
+    analyzed.len() - before
+}
+
+/// Brings the index in line with `path`, a file or directory inside the indexed `root`.
+/// Only files whose fingerprint changed since they were last indexed are analyzed again.
+pub fn update_index(
+    index: &mut MetaIndex,
+    root: &Path,
+    path: &Path,
+    rules: &IndexRules,
+    limit: Option<usize>,
+) -> Result<IndexSummary> {
+    let entries = if path.is_dir() {
+        get_entries_below(
+            root,
+            path,
+            limit.unwrap_or(usize::MAX),
+            &rules.ignorer,
+            |entry| rules.allows(entry),
+        )?
+    } else {
+        Some(path.to_path_buf())
+            .filter(|path| rules.allows(path) && !rules.ignorer.is_ignored(root, path, false))
+            .into_iter()
+            .collect()
+    };
+    let diff = diff_fingerprints(index.fingerprints_under(path)?, entries);
+
+    let (mut analyzed, mut failed) = (Vec::new(), Vec::new());
+    let (added, modified) = (
+        analyze_stale(&diff.added, &mut analyzed, &mut failed),
+        analyze_stale(&diff.modified, &mut analyzed, &mut failed),
+    );
+
+    index.upsert_all(
+        analyzed
+            .iter()
+            .map(|(meta, fingerprint)| (meta, Some(*fingerprint))),
+    )?;
+    // remembered, so they count as unchanged until they change again
+    index.mark_no_metadata(&failed)?;
+    let removed = index.remove_all(&diff.removed)?;
+
+    Ok(IndexSummary {
+        added,
+        modified,
+        removed,
+        unchanged: diff.unchanged,
+        failed: failed.len(),
+    })
+}
+
+pub fn dispatch_index(
+    IndexArgs {
+        filter,
+        limit,
+        root,
+    }: IndexArgs,
+    config: &BuoConfig,
+) -> Result<()> {
+    if !root.is_dir() {
+        bail!("{} is not a directory!", root.display());
+    }
+    // indexed paths are absolute so re-runs from another directory line up
+    let root = root.canonicalize()?;
+
+    let index_path = config.index_path()?;
+    let mut index = MetaIndex::open(&index_path)?;
+
+    let rules = IndexRules::new(config, FileFilter::from(filter))?;
+    let summary = update_index(&mut index, &root, &root, &rules, limit)?;
+    println!("{} in {}", summary, index_path.display());
+    Ok(())
+}
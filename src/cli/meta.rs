@@ -0,0 +1,367 @@
+use super::{
+    args::MetaArgs,
+    output::{apply_output_config, Printer},
+};
+use crate::{
+    prelude::*,
+    util::{
+        cache::{retrieve_configured_cache, update_cache, CacheKey, PersistentCache},
+        config::{AnalyzerConfig, BuoConfig, CacheConfig},
+        dirs::get_dir_meta,
+        filter::FileFilter,
+        json_out::ExportedJson,
+        media::dispatch_meta_fn,
+        pool::{ordered_par_map, panic_message},
+        walk::{collect_files, Ignorer},
+    },
+};
+use std::{
+    io::{stdin, BufRead, BufReader},
+    iter::once,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const STDIN_TARGET: &str = "-";
+
+/// Result of inspecting a single target, failures included
+pub enum TargetOutcome {
+    File(MediaMeta),
+    Dir(DirMeta),
+    /// an explicit target the filters exclude
+    Skipped,
+    Unsupported(String),
+    NoMetadata,
+    Failed(anyhow::Error),
+}
+
+impl TargetOutcome {
+    pub fn inspect(target: &Path, ignorer: &Ignorer) -> Self {
+        // missing or unreadable targets are io errors, whatever their extension
+        if let Err(e) = target.symlink_metadata() {
+            return Self::Failed(e.into());
+        }
+
+        // all directories get same treatment, dynamic dispatch not needed
+        if target.is_dir() {
+            return match get_dir_meta(target, ignorer) {
+                Ok(dir_meta) => Self::Dir(dir_meta),
+                Err(e) => Self::Failed(e),
+            };
+        }
+
+        let dispatcher = match dispatch_meta_fn(target) {
+            Some(dispatcher) => dispatcher,
+            None => {
+                // No filetype associated callback found
+                let file_type = get_file_ext(target).unwrap_or("unknown");
+                return Self::Unsupported(file_type.to_owned());
+            }
+        };
+
+        // FileExtCallback found, dynamically dispatching
+        match dispatcher.try_get_meta(target) {
+            Ok(Some(mut meta)) => {
+                meta.file_path = target.to_path_buf();
+                Self::File(meta)
+            }
+            Ok(None) => Self::NoMetadata,
+            Err(e) => Self::Failed(e),
+        }
+    }
+
+    pub fn print(self, target: PathBuf, printer: &mut Printer) -> Result<()> {
+        match self {
+            Self::File(meta) => printer.print(ExportedJson::from(meta)),
+            Self::Dir(dir_meta) => printer.print(ExportedJson::from(dir_meta)),
+            Self::Unsupported(file_type) => {
+                printer.print(ExportedJson::unsupported(target, &file_type))
+            }
+            Self::Skipped => printer.print(ExportedJson::skipped(target)),
+            Self::NoMetadata => printer.print(ExportedJson::no_metadata(target)),
+            Self::Failed(e) => printer.print(ExportedJson::failed(target, &e)),
+        }
+    }
+}
+
+/// A file looked up in the persistent cache, recorded there once every target is done
+struct CacheLookup {
+    key: CacheKey,
+    /// what the file was analyzed to on a miss
+    meta: Option<MediaMeta>,
+}
+
+/// What the inspection of every target shares
+struct Inspector {
+    ignorer: Ignorer,
+    /// the persistent cache as it was before the first target, `None` if it can't be read
+    cache: Option<PersistentCache>,
+}
+
+impl Inspector {
+    /// Like `TargetOutcome::inspect`, reading files unchanged since they were cached from the cache
+    fn inspect(&self, target: &Path) -> (TargetOutcome, Option<CacheLookup>) {
+        let cached = self.cache.as_ref().filter(|_| {
+            // keeps directories and unsupported files out of the hit rate
+            target.is_file() && dispatch_meta_fn(target).is_some()
+        });
+        let (cache, key) = match cached.zip(CacheKey::of(target)) {
+            Some(cached) => cached,
+            None => return (TargetOutcome::inspect(target, &self.ignorer), None),
+        };
+
+        if let Some(meta) = cache.peek(&key) {
+            let meta = MediaMeta {
+                file_path: target.to_path_buf(),
+                ..meta.clone()
+            };
+            return (
+                TargetOutcome::File(meta),
+                Some(CacheLookup { key, meta: None }),
+            );
+        }
+
+        let outcome = TargetOutcome::inspect(target, &self.ignorer);
+        let meta = match outcome {
+            // entries are listed by the canonical path they are found by
+            TargetOutcome::File(ref meta) => Some(MediaMeta {
+                file_path: key.path().to_path_buf(),
+                ..meta.clone()
+            }),
+            _ => None,
+        };
+        (outcome, Some(CacheLookup { key, meta }))
+    }
+}
+
+/// Replays the lookups made through the cache snapshot, counting hits and misses and caching
+/// the files analyzed on a miss
+fn record_lookups(
+    cache_path: &Path,
+    config: &CacheConfig,
+    lookups: Vec<CacheLookup>,
+) -> Result<()> {
+    update_cache(cache_path, config, |cache| {
+        for CacheLookup { key, meta } in lookups {
+            if cache.get_keyed(&key).is_some() {
+                continue;
+            }
+            if let Some(meta) = meta {
+                cache.insert_keyed(key, meta);
+            }
+        }
+    })
+}
+
+/// A target waiting to be inspected, or one whose outcome is already known
+enum MetaJob {
+    Inspect(PathBuf, Arc<Inspector>),
+    Done(PathBuf, TargetOutcome),
+}
+
+impl MetaJob {
+    fn run(self) -> (PathBuf, TargetOutcome, Option<CacheLookup>) {
+        match self {
+            Self::Inspect(target, inspector) => {
+                // parsers may panic on malformed media, which fails only this target
+                let (outcome, lookup) =
+                    catch_unwind(AssertUnwindSafe(|| inspector.inspect(&target))).unwrap_or_else(
+                        |panic| {
+                            let message = panic_message(&*panic);
+                            (
+                                TargetOutcome::Failed(anyhow!("Analyzer panicked: {}", message)),
+                                None,
+                            )
+                        },
+                    );
+                (target, outcome, lookup)
+            }
+            Self::Done(target, outcome) => (target, outcome, None),
+        }
+    }
+}
+
+struct WalkOptions {
+    recursive: bool,
+    max_depth: Option<usize>,
+    inspector: Arc<Inspector>,
+    analyzers: AnalyzerConfig,
+    filter: FileFilter,
+}
+
+/// Expands recursive directory targets into jobs for every supported file
+fn expand_target(target: PathBuf, walk_options: &WalkOptions) -> Vec<MetaJob> {
+    let is_supported =
+        |file: &Path| dispatch_meta_fn(file).is_some() && walk_options.analyzers.allows_path(file);
+
+    if !target.is_dir() {
+        // explicit targets still get a record, unlike files filtered out of a walk
+        if !walk_options.filter.matches(&target) {
+            return vec![MetaJob::Done(target, TargetOutcome::Skipped)];
+        }
+
+        // files of a disabled analyzer are reported as unsupported
+        if dispatch_meta_fn(&target).is_some() && !is_supported(&target) {
+            let file_type = get_file_ext(&target).unwrap_or("unknown").to_owned();
+            return vec![MetaJob::Done(target, TargetOutcome::Unsupported(file_type))];
+        }
+        return vec![MetaJob::Inspect(
+            target,
+            Arc::clone(&walk_options.inspector),
+        )];
+    } else if !walk_options.recursive {
+        return vec![MetaJob::Inspect(
+            target,
+            Arc::clone(&walk_options.inspector),
+        )];
+    }
+
+    let ignorer = &walk_options.inspector.ignorer;
+    match collect_files(&target, walk_options.max_depth, ignorer) {
+        // only supported files are of interest when walking a tree
+        Ok(files) => files
+            .into_iter()
+            .filter(|file| walk_options.filter.matches(file) && is_supported(file))
+            .map(|file| MetaJob::Inspect(file, Arc::clone(&walk_options.inspector)))
+            .collect(),
+        Err(e) => vec![MetaJob::Done(target, TargetOutcome::Failed(e))],
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+    OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+/// Lazily reads newline or NUL separated paths, so results stream as paths arrive
+fn stdin_targets(null_separated: bool) -> impl Iterator<Item = Result<PathBuf>> + Send {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    BufReader::new(stdin())
+        .split(separator)
+        // a read error repeats on every later read, so it ends the targets
+        .scan(false, |failed, line| {
+            if *failed {
+                return None;
+            }
+            *failed = line.is_err();
+            Some(line)
+        })
+        .map(|line| {
+            let mut line = line?;
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            Ok(line)
+        })
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .map(|line| line.map(path_from_bytes))
+}
+
+type TargetIter = Box<dyn Iterator<Item = Result<PathBuf>> + Send>;
+
+/// Replaces `-` with the paths read from stdin, at most once
+fn resolve_targets(
+    target_files: Vec<PathBuf>,
+    read_stdin: bool,
+    null_separated: bool,
+) -> TargetIter {
+    let mut stdin_pending = true;
+    let mut targets: TargetIter = Box::new(target_files.into_iter().flat_map(move |target| {
+        if target.as_os_str() == STDIN_TARGET && stdin_pending {
+            stdin_pending = false;
+            Box::new(stdin_targets(null_separated)) as TargetIter
+        } else {
+            Box::new(once(Ok(target)))
+        }
+    }));
+
+    if read_stdin {
+        targets = Box::new(targets.chain(stdin_targets(null_separated)));
+    }
+    targets
+}
+
+pub fn dispatch_meta(
+    MetaArgs {
+        mut output,
+        filter,
+        recursive,
+        max_depth,
+        jobs,
+        stdin,
+        null,
+        target_files,
+    }: MetaArgs,
+    config: &BuoConfig,
+) -> Result<i32> {
+    if target_files.is_empty() && !stdin {
+        bail!("No target files provided!")
+    }
+    if stdin
+        && target_files
+            .iter()
+            .any(|target| target.as_os_str() == STDIN_TARGET)
+    {
+        bail!("Stdin can only be read once, pass either `-` or --stdin");
+    }
+    apply_output_config(&mut output, &config.output)?;
+
+    // metadata can still be read without the cache, only slower
+    let cached = config.cache_path().and_then(|cache_path| {
+        let cache = retrieve_configured_cache(&cache_path, &config.cache)?;
+        Ok((cache_path, cache))
+    });
+    let (cache_path, cache) = match cached {
+        Ok((cache_path, cache)) => (Some(cache_path), Some(cache)),
+        Err(e) => {
+            eprintln!("Not using the cache: {}", e);
+            (None, None)
+        }
+    };
+
+    let walk_options = WalkOptions {
+        recursive,
+        max_depth,
+        inspector: Arc::new(Inspector {
+            ignorer: config.ignorer()?,
+            cache,
+        }),
+        analyzers: config.analyzers,
+        filter: filter.into(),
+    };
+    let meta_jobs =
+        resolve_targets(target_files, stdin, null).flat_map(move |target| match target {
+            Ok(target) => expand_target(target, &walk_options),
+            Err(e) => vec![MetaJob::Done(
+                PathBuf::from(STDIN_TARGET),
+                TargetOutcome::Failed(e),
+            )],
+        });
+
+    let mut printer = Printer::new(&output);
+    let mut lookups = Vec::new();
+    ordered_par_map(
+        meta_jobs,
+        jobs,
+        MetaJob::run,
+        |(target, outcome, lookup)| {
+            lookups.extend(lookup);
+            outcome.print(target, &mut printer)
+        },
+    )?;
+
+    if let Some(cache_path) = cache_path.filter(|_| !lookups.is_empty()) {
+        if let Err(e) = record_lookups(&cache_path, &config.cache, lookups) {
+            eprintln!("Unable to update the cache: {}", e);
+        }
+    }
+    printer.finish()
+}
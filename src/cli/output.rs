@@ -0,0 +1,163 @@
+use super::args::OutputArgs;
+use crate::{
+    prelude::*,
+    util::{
+        config::{OutputConfig, OutputKind},
+        delimited::{delimited_header, DelimitedRecord},
+        json_out::{ExportStatus, ExportedJson},
+        template::{Template, TemplateRecord},
+    },
+};
+use std::fmt;
+
+pub const EXIT_SUCCESS: i32 = 0;
+const EXIT_UNSUPPORTED: i32 = 4;
+const EXIT_NO_METADATA: i32 = 8;
+const EXIT_PARSE_ERROR: i32 = 16;
+const EXIT_IO_ERROR: i32 = 32;
+
+/// Every status seen sets its own bit, so the exit code summarizes the whole run
+fn exit_code_bit(status: ExportStatus) -> i32 {
+    match status {
+        ExportStatus::Ok | ExportStatus::Skipped => EXIT_SUCCESS,
+        ExportStatus::Unsupported => EXIT_UNSUPPORTED,
+        ExportStatus::NoMetadata => EXIT_NO_METADATA,
+        ExportStatus::ParseError => EXIT_PARSE_ERROR,
+        ExportStatus::IoError => EXIT_IO_ERROR,
+    }
+}
+
+/// Fills in the configured output format when no output flag was passed
+pub fn apply_output_config(output: &mut OutputArgs, config: &OutputConfig) -> Result<()> {
+    let OutputArgs {
+        json,
+        array,
+        prettify,
+        csv,
+        tsv,
+        ref format,
+        ..
+    } = *output;
+
+    output.no_header |= !config.header;
+    if json || array || prettify || csv || tsv || format.is_some() {
+        return Ok(());
+    }
+
+    if let Some(ref template) = config.template {
+        output.format = Some(template.parse()?);
+        return Ok(());
+    }
+
+    match config.format {
+        Some(OutputKind::Json) => output.json = true,
+        Some(OutputKind::Array) => output.array = true,
+        Some(OutputKind::Pretty) => output.prettify = true,
+        Some(OutputKind::Csv) => output.csv = true,
+        Some(OutputKind::Tsv) => output.tsv = true,
+        Some(OutputKind::Text) | None => {}
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat<'out> {
+    Text,
+    Template(&'out Template),
+    Json,
+    PrettyJson,
+    Delimited(char),
+}
+
+/// Writes records to stdout in the format requested on the command line.
+/// Array output is streamed, so `finish` must be called to close the array.
+pub struct Printer<'out> {
+    format: OutputFormat<'out>,
+    array: bool,
+    header: bool,
+    records: usize,
+    exit_code: i32,
+}
+
+impl<'out> Printer<'out> {
+    pub fn new(output: &'out OutputArgs) -> Self {
+        let format = if let Some(ref template) = output.format {
+            OutputFormat::Template(template)
+        } else if output.csv {
+            OutputFormat::Delimited(',')
+        } else if output.tsv {
+            OutputFormat::Delimited('\t')
+        } else if output.prettify {
+            OutputFormat::PrettyJson
+        } else if output.json || output.array {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Text
+        };
+
+        Self {
+            format,
+            array: output.array && matches!(format, OutputFormat::Json | OutputFormat::PrettyJson),
+            header: !output.no_header,
+            records: 0,
+            exit_code: EXIT_SUCCESS,
+        }
+    }
+
+    fn print_header(&self) {
+        if let OutputFormat::Delimited(delimiter) = self.format {
+            if self.header {
+                println!("{}", delimited_header(delimiter));
+            }
+        }
+    }
+
+    pub fn print<T>(&mut self, record: ExportedJson<T>) -> Result<()>
+    where
+        T: Serialize + fmt::Display + DelimitedRecord + TemplateRecord,
+    {
+        if self.records == 0 {
+            self.print_header();
+        }
+        self.exit_code |= exit_code_bit(record.status());
+
+        // plain text failures belong on stderr, machine readable ones are records
+        if self.format == OutputFormat::Text && record.status().is_error() {
+            eprintln!("{}", record);
+            return Ok(());
+        }
+
+        let formatted_output = match self.format {
+            OutputFormat::Text => record.to_string(),
+            OutputFormat::Template(template) => record.render(template),
+            OutputFormat::Json => record.as_json()?,
+            OutputFormat::PrettyJson => record.as_pretty_json()?,
+            OutputFormat::Delimited(delimiter) => record.as_delimited(delimiter),
+        };
+
+        if self.array {
+            let separator = if self.records == 0 { "[\n" } else { ",\n" };
+            print!("{}{}", separator, formatted_output);
+        } else {
+            println!("{}", formatted_output);
+        }
+
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Closes the output and returns the exit code summarizing every record
+    pub fn finish(self) -> Result<i32> {
+        if self.records == 0 {
+            self.print_header();
+        }
+
+        if self.array {
+            match self.records {
+                0 => println!("[]"),
+                _ => println!("\n]"),
+            }
+        }
+        Ok(self.exit_code)
+    }
+}
@@ -0,0 +1,72 @@
+use super::{
+    args::SearchArgs,
+    output::{apply_output_config, Printer},
+};
+use crate::{
+    prelude::*,
+    util::{
+        config::BuoConfig,
+        history::{frecencies, rank_hits, unix_now},
+        index::{MetaIndex, SearchHit},
+        query::Query,
+        ranker::Ranker,
+    },
+};
+
+/// Restores the quotes the shell strips, so `author:"Daft Punk"` stays a single clause
+fn quote_arg(arg: &str) -> String {
+    if !arg.contains(char::is_whitespace) || arg.contains('"') {
+        return arg.to_owned();
+    }
+
+    match arg.find(|c: char| ":=<>".contains(c)) {
+        Some(op)
+            if arg[..op]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            let value_start = op + arg[op..].find(|c: char| !"=<>:".contains(c)).unwrap_or(0);
+            format!("{}\"{}\"", &arg[..value_start], &arg[value_start..])
+        }
+        _ => format!("\"{}\"", arg),
+    }
+}
+
+pub fn dispatch_search(
+    SearchArgs { mut output, terms }: SearchArgs,
+    config: &BuoConfig,
+) -> Result<i32> {
+    apply_output_config(&mut output, &config.output)?;
+
+    let terms: Vec<String> = terms.iter().map(|term| quote_arg(term)).collect();
+    let query: Query = terms.join(" ").parse()?;
+    let index = MetaIndex::open(&config.index_path()?)?;
+
+    // full text search narrows down candidates when there are words to match
+    let words: Vec<String> = query.words().map(str::to_owned).collect();
+    let mut hits = if words.is_empty() {
+        index
+            .entries()?
+            .into_iter()
+            .map(|meta| SearchHit::new(meta, None))
+            .collect()
+    } else {
+        index.search(&words)?
+    };
+    rank_hits(
+        &mut hits,
+        &frecencies(&index, &config.history)?,
+        &config.history,
+    );
+    if config.ranker.enabled {
+        Ranker::load(&config.ranker_path()?)?.rank_hits(&mut hits, unix_now());
+    }
+
+    let mut printer = Printer::new(&output);
+    for hit in hits {
+        if query.matches_fields(&hit.meta) {
+            printer.print(hit.into())?;
+        }
+    }
+    printer.finish()
+}
@@ -0,0 +1,22 @@
+use super::args::VisitArgs;
+use crate::{
+    prelude::*,
+    util::{config::BuoConfig, history::unix_now, index::MetaIndex},
+};
+
+pub fn dispatch_visit(VisitArgs { paths }: VisitArgs, config: &BuoConfig) -> Result<()> {
+    let mut index = MetaIndex::open(&config.index_path()?)?;
+    let now = unix_now();
+
+    for path in paths {
+        // the index only holds canonical paths
+        let recorded = match path.canonicalize() {
+            Ok(canonical) => index.record_visit(&canonical, now)?,
+            Err(_) => false,
+        };
+        if !recorded {
+            eprintln!("{} is not indexed", path.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use super::{
+    args::WatchArgs,
+    index::{update_index, IndexRules, IndexSummary},
+};
+use crate::{
+    prelude::*,
+    util::{config::BuoConfig, filter::FileFilter, index::MetaIndex, walk::IGNORE_FILES},
+};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::read_dir,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How often the inotify queue is drained while waiting for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// A burst that never quiets down is still flushed after this many debounce periods
+const MAX_DEBOUNCES: u32 = 10;
+
+fn watch_mask() -> WatchMask {
+    WatchMask::CLOSE_WRITE
+        | WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+}
+
+/// inotify isn't recursive, so every directory below the roots gets its own watch
+struct Watcher {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    /// Watches `dir` and every directory below it that isn't ignored
+    fn watch_tree(&mut self, root: &Path, dir: &Path, rules: &IndexRules) {
+        // a root is watched even when its parents' ignore files exclude it
+        if dir != root && rules.ignorer.is_ignored(root, dir, true) {
+            return;
+        }
+
+        match self.inotify.add_watch(dir, watch_mask()) {
+            Ok(wd) => {
+                self.dirs.insert(wd, dir.to_path_buf());
+            }
+            Err(e) => {
+                // usually fs.inotify.max_user_watches running out on large trees
+                eprintln!("Unable to watch {}: {}", dir.display(), e);
+                return;
+            }
+        }
+
+        let dir_ents = match read_dir(dir) {
+            Ok(dir_ents) => dir_ents,
+            Err(_) => return,
+        };
+        for ent in dir_ents.filter_map(|ent| ent.ok()) {
+            // symlinked directories aren't followed, just as the indexer's walk skips them
+            if ent
+                .file_type()
+                .ok()
+                .filter(|file_type| file_type.is_dir())
+                .is_some()
+            {
+                self.watch_tree(root, &ent.path(), rules);
+            }
+        }
+    }
+
+    /// Drops the watches of a directory moved away, its new location is watched afresh
+    fn unwatch_tree(&mut self, dir: &Path) {
+        let moved: Vec<_> = self
+            .dirs
+            .iter()
+            .filter(|(_, path)| path.starts_with(dir))
+            .map(|(wd, _)| wd.clone())
+            .collect();
+
+        for wd in moved {
+            self.dirs.remove(&wd);
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+}
+
+/// Re-indexes every changed path, returning what changed overall
+fn flush(
+    pending: BTreeSet<PathBuf>,
+    roots: &[PathBuf],
+    watcher: &mut Watcher,
+    index: &mut MetaIndex,
+    rules: &IndexRules,
+) -> IndexSummary {
+    let mut summary = IndexSummary::default();
+    // paths sort right before everything below them, so a changed directory covers its contents
+    let mut covered: Option<PathBuf> = None;
+
+    for path in pending {
+        if covered
+            .as_ref()
+            .filter(|dir| path.starts_with(dir))
+            .is_some()
+        {
+            continue;
+        }
+        let root = match roots.iter().find(|root| path.starts_with(root)) {
+            Some(root) => root,
+            None => continue,
+        };
+
+        let is_dir = path.is_dir();
+        if path != *root && rules.ignorer.is_ignored(root, &path, is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            watcher.watch_tree(root, &path, rules);
+        }
+        match update_index(index, root, &path, rules, None) {
+            Ok(changes) => summary += changes,
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+        covered = Some(path);
+    }
+    summary
+}
+
+pub fn dispatch_watch(WatchArgs { filter, roots }: WatchArgs, config: &BuoConfig) -> Result<()> {
+    let roots = if roots.is_empty() {
+        config.watch.roots.clone()
+    } else {
+        roots
+    };
+    if roots.is_empty() {
+        bail!("No directories to watch, pass some or set `roots` under [watch] in the config");
+    }
+
+    let roots = roots
+        .iter()
+        .map(|root| {
+            if !root.is_dir() {
+                bail!("{} is not a directory!", root.display());
+            }
+            // events carry absolute paths, and so does the index
+            Ok(root.canonicalize()?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let index_path = config.index_path()?;
+    let mut index = MetaIndex::open(&index_path)?;
+    let rules = IndexRules::new(config, FileFilter::from(filter))?;
+
+    let mut watcher = Watcher {
+        inotify: Inotify::init()?,
+        dirs: HashMap::new(),
+    };
+    for root in &roots {
+        watcher.watch_tree(root, root, &rules);
+        // catches up on whatever changed while nothing was watching
+        let summary = update_index(&mut index, root, root, &rules, None)?;
+        println!("{} in {}", summary, root.display());
+    }
+    eprintln!(
+        "Watching {} directories for {}, press Ctrl-C to stop",
+        watcher.dirs.len(),
+        index_path.display()
+    );
+
+    let debounce = Duration::from_millis(config.watch.debounce_ms);
+    let mut buffer = [0; 4096];
+    let mut pending = BTreeSet::new();
+    let (mut first_change, mut last_change) = (Instant::now(), Instant::now());
+
+    loop {
+        let mut changed = false;
+        for event in watcher.inotify.read_events(&mut buffer)? {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                // events were dropped, so only a rescan of everything is reliable
+                pending.extend(roots.iter().cloned());
+                changed = true;
+                continue;
+            }
+            if event.mask.contains(EventMask::IGNORED) {
+                watcher.dirs.remove(&event.wd);
+                continue;
+            }
+
+            let dir = match watcher.dirs.get(&event.wd) {
+                Some(dir) => dir,
+                None => continue,
+            };
+            let path = match event.name {
+                Some(name) => dir.join(name),
+                None => dir.clone(),
+            };
+            if pending.is_empty() {
+                first_change = Instant::now();
+            }
+            let ignore_file = event
+                .name
+                .filter(|name| IGNORE_FILES.iter().any(|file| name == file))
+                .is_some();
+            if ignore_file {
+                // rescanning the directory picks up files the old rules hid
+                rules.ignorer.forget(dir);
+                pending.insert(dir.clone());
+            }
+            if event
+                .mask
+                .contains(EventMask::MOVED_FROM | EventMask::ISDIR)
+            {
+                watcher.unwatch_tree(&path);
+            }
+            pending.insert(path);
+            changed = true;
+        }
+        if changed {
+            last_change = Instant::now();
+        }
+
+        let settled =
+            last_change.elapsed() >= debounce || first_change.elapsed() >= debounce * MAX_DEBOUNCES;
+        if !pending.is_empty() && settled {
+            let changes = std::mem::take(&mut pending);
+            let summary = flush(changes, &roots, &mut watcher, &mut index, &rules);
+            if summary.has_changes() {
+                println!("{}", summary);
+            }
+        } else {
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
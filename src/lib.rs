@@ -2,13 +2,22 @@ pub(crate) mod prelude;
 pub(crate) mod util;
 pub use util::{
     cache::{
-        commit_cache_to_path, get_initial_entries, replace_invalid_entries, retrieve_or_init_cache,
-        LiveCache, PersistentCache, MAX_CACHE_SIZE,
+        commit_cache_to_path, default_cache_path, get_initial_entries, get_initial_entries_with,
+        replace_invalid_entries, replace_invalid_entries_with, retrieve_configured_cache,
+        retrieve_or_init_cache, update_cache, CacheKey, CacheStats, EvictionPolicy, LiveCache,
+        PersistentCache, MAX_CACHE_SIZE,
     },
+    config::CacheConfig,
     dev::LangStats,
     dirs::DirMeta,
-    json_out::{ExportKind, ExportedJson},
+    dupes::{find_dupes, DupeGroup},
+    history::{frecency, rank_hits},
+    index::{default_index_path, MetaIndex, SearchHit},
+    json_out::{ExportKind, ExportStatus, ExportedJson, TargetIssue},
     media::meta::MediaMeta,
+    query::{Query, QueryError, QueryRecord},
+    ranker::{default_ranker_path, Ranker, Sample},
+    walk::{Ignorer, IGNORE_FILES},
 };
 
 use anyhow::{bail, Result};
@@ -26,10 +35,15 @@ pub fn buo_media_query(query: &Path) -> Result<Option<MediaMeta>> {
 }
 
 pub fn buo_dir_meta(query: &Path) -> Result<DirMeta> {
+    buo_dir_meta_with(query, &Ignorer::none())
+}
+
+/// Like `buo_dir_meta`, but skipping the paths `ignorer` ignores
+pub fn buo_dir_meta_with(query: &Path, ignorer: &Ignorer) -> Result<DirMeta> {
     if !query.is_dir() {
         bail!("{} is not a directory!", query.display());
     }
-    get_dir_meta(query)
+    get_dir_meta(query, ignorer)
 }
 
 pub fn force_init_cache(path: &Path) -> Result<()> {
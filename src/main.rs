@@ -8,5 +8,6 @@ mod util;
 
 fn main() -> Result<()> {
     let buo_args = fetch_cli_args()?;
-    dispatch_from_cli(buo_args)
+    let exit_code = dispatch_from_cli(buo_args)?;
+    std::process::exit(exit_code)
 }
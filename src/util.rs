@@ -1,11 +1,23 @@
 pub mod cache;
+pub mod config;
+pub mod delimited;
 pub mod dev;
 pub mod dirs;
+pub mod dupes;
 pub mod file_types;
+pub mod filter;
+pub mod fingerprint;
+pub mod history;
+pub mod index;
 pub mod iso4;
 pub mod json_out;
 pub mod media;
 pub mod os;
+pub mod pool;
+pub mod query;
+pub mod ranker;
+pub mod template;
 pub mod text;
 pub mod traits;
+pub mod walk;
 pub mod web;
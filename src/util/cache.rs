@@ -1,62 +1,99 @@
+mod format;
 pub mod local;
+mod lock;
 pub mod session;
 
-pub use local::{commit_cache_to_path, retrieve_or_init_cache, PersistentCache, MAX_CACHE_SIZE};
+pub use local::{
+    commit_cache_to_path, retrieve_configured_cache, retrieve_or_init_cache, update_cache,
+    CacheKey, CacheStats, EvictionPolicy, PersistentCache, MAX_CACHE_SIZE,
+};
 pub use session::LiveCache;
 
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    util::walk::{collect_files_below, Ignorer},
+};
 use std::{
-    fs::read_dir,
+    cmp::Reverse,
     path::{Path, PathBuf},
 };
 
-/// Recursively accrues paths validated by a closure, and fails eagerly
-fn acc_valid_paths(
-    dir_path: &Path,
-    acc: &mut Vec<PathBuf>,
-    len_limit: &mut usize,
+const CACHE_DIR: &str = "buo";
+const CACHE_FILE: &str = "buo.cache";
+
+/// Location of the persistent cache, creating its parent directory if needed
+pub fn default_cache_path() -> Result<PathBuf> {
+    let mut cache_path = dirs::cache_dir().ok_or_else(|| anyhow!("Unable to locate cache dir"))?;
+    cache_path.push(CACHE_DIR);
+    std::fs::create_dir_all(&cache_path)?;
+
+    cache_path.push(CACHE_FILE);
+    Ok(cache_path)
+}
+
+pub fn replace_invalid_entries(
+    root_path: &Path,
+    paths: &mut [PathBuf],
     validator: impl Fn(&Path) -> bool,
 ) -> Result<()> {
-    for ent in read_dir(dir_path)? {
-        let path = ent?.path();
-        if path.is_dir() {
-            acc_valid_paths(&path, acc, len_limit, &validator)?;
-        }
-
-        if validator(&path) {
-            *len_limit -= 1;
-            acc.push(path);
-        }
-        if *len_limit == 0 {
-            return Ok(());
-        }
-    }
-    Ok(())
+    replace_invalid_entries_with(root_path, paths, &Ignorer::none(), validator)
 }
 
-pub fn replace_invalid_entries(
+/// Like `replace_invalid_entries`, only replacing entries with files `ignorer` doesn't skip
+pub fn replace_invalid_entries_with(
     root_path: &Path,
     paths: &mut [PathBuf],
+    ignorer: &Ignorer,
     validator: impl Fn(&Path) -> bool,
 ) -> Result<()> {
-    paths.sort_unstable_by(|p1, p2| validator(p2).cmp(&validator(p1)));
+    // valid entries first
+    paths.sort_unstable_by_key(|path| Reverse(validator(path)));
 
     if let Some(first_invalid_entry) = paths.iter().position(|p| !validator(p)) {
-        let mut len_limit = paths.len() - first_invalid_entry;
-        let mut new_valid_paths = Vec::with_capacity(len_limit);
+        let len_limit = paths.len() - first_invalid_entry;
+        let mut new_valid_paths =
+            get_entries_below(root_path, root_path, len_limit, ignorer, validator)?;
 
-        acc_valid_paths(root_path, &mut new_valid_paths, &mut len_limit, validator)?;
-        paths[first_invalid_entry..].swap_with_slice(new_valid_paths.as_mut_slice());
+        let replaced = first_invalid_entry..first_invalid_entry + new_valid_paths.len();
+        paths[replaced].swap_with_slice(new_valid_paths.as_mut_slice());
     }
     Ok(())
 }
 
 pub fn get_initial_entries(
     root_path: &Path,
-    mut len_limit: usize,
+    len_limit: usize,
     validator: impl Fn(&Path) -> bool,
 ) -> Result<Vec<PathBuf>> {
-    let mut entries = Vec::with_capacity(len_limit);
-    acc_valid_paths(root_path, &mut entries, &mut len_limit, validator)?;
-    Ok(entries)
+    get_initial_entries_with(root_path, len_limit, &Ignorer::none(), validator)
+}
+
+/// Like `get_initial_entries`, skipping the paths `ignorer` ignores
+pub fn get_initial_entries_with(
+    root_path: &Path,
+    len_limit: usize,
+    ignorer: &Ignorer,
+    validator: impl Fn(&Path) -> bool,
+) -> Result<Vec<PathBuf>> {
+    get_entries_below(root_path, root_path, len_limit, ignorer, validator)
+}
+
+/// The first `len_limit` files below `dir_path` that `validator` accepts, walked as
+/// `walk::collect_files` walks `root_path`, so every command agrees on the files of a tree
+pub fn get_entries_below(
+    root_path: &Path,
+    dir_path: &Path,
+    len_limit: usize,
+    ignorer: &Ignorer,
+    validator: impl Fn(&Path) -> bool,
+) -> Result<Vec<PathBuf>> {
+    if len_limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    Ok(collect_files_below(root_path, dir_path, None, ignorer)?
+        .into_iter()
+        .filter(|path| validator(path))
+        .take(len_limit)
+        .collect())
 }
@@ -0,0 +1,181 @@
+use super::local::PersistentCache;
+use crate::prelude::*;
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use std::{collections::HashMap, convert::TryInto, fmt, path::Path};
+
+/// Start of every cache file written since the format got a header
+const MAGIC: &[u8; 8] = b"BUOCACHE";
+/// Bumped whenever the serialized `PersistentCache` or `MediaMeta` change shape,
+/// alongside a migration from the previous version in `decode`
+pub const FORMAT_VERSION: u32 = 1;
+const CHECKSUM_LEN: usize = blake3::OUT_LEN;
+const HEADER_LEN: usize = MAGIC.len() + 4 + CHECKSUM_LEN;
+
+/// Capacity of the fixed size cache written before caches had a header
+const LEGACY_CACHE_SIZE: usize = 1200;
+
+/// Version 0: a headerless cache keyed by the paths as given, with a fixed number of slots
+#[derive(Deserialize)]
+struct LegacyCache {
+    cache_lookup: HashMap<String, usize>,
+    _last_inserted_index: usize,
+    #[serde(deserialize_with = "legacy_slots")]
+    entries: Vec<Option<MediaMeta>>,
+}
+
+/// The slots were a fixed size array, read onto the heap since they outgrow test thread stacks
+fn legacy_slots<'de, D>(deserializer: D) -> std::result::Result<Vec<Option<MediaMeta>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct Slots;
+
+    impl<'de> Visitor<'de> for Slots {
+        type Value = Vec<Option<MediaMeta>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} cache slots", LEGACY_CACHE_SIZE)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut slots = Vec::with_capacity(LEGACY_CACHE_SIZE);
+            while let Some(slot) = seq.next_element()? {
+                slots.push(slot);
+            }
+            Ok(slots)
+        }
+    }
+
+    deserializer.deserialize_tuple(LEGACY_CACHE_SIZE, Slots)
+}
+
+impl LegacyCache {
+    /// Entries of files that still exist, fingerprinted as they are now since version 0 kept
+    /// no record of the state they were read in
+    fn migrate(self) -> PersistentCache {
+        let mut cache = PersistentCache::new();
+        let mut entries = self.entries;
+        for (key, index) in self.cache_lookup {
+            if let Some(meta) = entries.get_mut(index).and_then(Option::take) {
+                let _ = cache.insert(Path::new(&key), meta);
+            }
+        }
+        cache
+    }
+}
+
+/// Serializes `cache` behind a header of magic bytes, format version and a checksum of the rest
+pub fn encode(cache: &PersistentCache) -> Result<Vec<u8>> {
+    let payload = bincode::serialize(cache)?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(blake3::hash(&payload).as_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Reads a cache written by `encode` in this or any earlier format version,
+/// along with the version it was written in
+pub fn decode(bytes: &[u8]) -> Result<(PersistentCache, u32)> {
+    if !bytes.starts_with(MAGIC) {
+        let legacy: LegacyCache = bincode::deserialize(bytes)
+            .map_err(|_| anyhow!("Not a buo cache, or one damaged beyond recovery"))?;
+        return Ok((legacy.migrate(), 0));
+    }
+    if bytes.len() < HEADER_LEN {
+        bail!("Cache header is truncated");
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_LEN);
+    let version = u32::from_le_bytes(header[MAGIC.len()..MAGIC.len() + 4].try_into()?);
+    if version > FORMAT_VERSION {
+        bail!(
+            "Cache format version {} was written by a newer buo, this one reads up to version {}",
+            version,
+            FORMAT_VERSION
+        );
+    }
+    if blake3::hash(payload).as_bytes() != &header[MAGIC.len() + 4..] {
+        bail!("Cache checksum mismatch, the file is corrupt");
+    }
+
+    match version {
+        FORMAT_VERSION => Ok((bincode::deserialize(payload)?, version)),
+        _ => bail!("Unknown cache format version {}", version),
+    }
+}
+
+#[test]
+fn migrates_and_validates() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+    let (song, gone) = (dir.join("song.mp3"), dir.join("gone.mp3"));
+    std::fs::write(&song, "buo")?;
+
+    // laid out as bincode writes the old struct: lookup, last index, then every slot
+    let lookup: HashMap<_, _> = vec![
+        (song.display().to_string(), 0usize),
+        (gone.display().to_string(), 1),
+    ]
+    .into_iter()
+    .collect();
+    let mut legacy = bincode::serialize(&lookup)?;
+    legacy.extend(bincode::serialize(&1usize)?);
+    for slot in 0..LEGACY_CACHE_SIZE {
+        legacy.extend(bincode::serialize(
+            &Some(MediaMeta::default()).filter(|_| slot < 2),
+        )?);
+    }
+    let (mut migrated, version) = decode(&legacy)?;
+    assert_eq!(version, 0);
+    assert_eq!(migrated.len(), 1);
+    assert!(migrated.get(&song).is_some());
+
+    let mut bytes = encode(&migrated)?;
+    assert_eq!(decode(&bytes)?.0.len(), 1);
+
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert!(decode(&bytes).is_err());
+    bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    let newer = decode(&bytes).err().map(|e| e.to_string());
+    assert!(newer.filter(|e| e.contains("newer buo")).is_some());
+    Ok(())
+}
+
+#[test]
+fn roundtrips_dates() -> Result<()> {
+    use crate::util::media::meta::DateKind;
+    use chrono::{DateTime, Utc};
+
+    let tmp = tempfile::tempdir()?;
+    let (song, film) = (tmp.path().join("song.mp3"), tmp.path().join("film.mkv"));
+    std::fs::write(&song, "buo")?;
+    std::fs::write(&film, "buo")?;
+    let dated = |date| MediaMeta {
+        date: Some(date),
+        ..Default::default()
+    };
+
+    let mut cache = PersistentCache::new();
+    let released: DateTime<Utc> = "2021-03-14T15:09:26Z".parse()?;
+    cache.insert(&song, dated(DateKind::Chrono(released)))?;
+    cache.insert(&film, dated(DateKind::Sym("1999".to_owned())))?;
+
+    let (mut decoded, _) = decode(&encode(&cache)?)?;
+    let date_of = |meta: Option<&MediaMeta>| meta.and_then(|meta| meta.date.clone());
+    assert!(matches!(
+        date_of(decoded.get(&song)),
+        Some(DateKind::Chrono(date)) if date == released
+    ));
+    assert!(matches!(
+        date_of(decoded.get(&film)),
+        Some(DateKind::Sym(year)) if year == "1999"
+    ));
+    Ok(())
+}
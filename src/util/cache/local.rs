@@ -1,160 +1,727 @@
-use crate::prelude::*;
-use std::{collections::HashMap, path::Path};
+use crate::{
+    prelude::*,
+    util::{config::CacheConfig, fingerprint::Fingerprint},
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
 
+/// Capacity of caches created without an explicit one
 pub const MAX_CACHE_SIZE: usize = 1200;
 
-#[serde_with::serde_as]
+/// Which entry makes room when a full cache gets a new one
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// least recently used
+    Lru,
+    /// least frequently used, ties go to the least recently used
+    Lfu,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        Self::Lru
+    }
+}
+
+/// Counters kept since the cache was created
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    insertions: u64,
+    evictions: u64,
+}
+
+impl CacheStats {
+    /// Lookups that found an up to date entry
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups of files not cached, or modified or deleted since
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn insertions(&self) -> u64 {
+        self.insertions
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct CacheEntry {
+    /// canonical path of the file
+    key: PathBuf,
+    /// state of the file when `meta` was read from it
+    fingerprint: Fingerprint,
+    meta: MediaMeta,
+    /// tick of the cache clock at the last insert or lookup
+    last_access: u64,
+    accesses: u64,
+}
+
+/// Position of an entry in the eviction order, lowest goes first
+type Rank = (u64, u64);
+
+fn rank(policy: EvictionPolicy, entry: &CacheEntry) -> Rank {
+    match policy {
+        EvictionPolicy::Lru => (entry.last_access, 0),
+        EvictionPolicy::Lfu => (entry.accesses, entry.last_access),
+    }
+}
+
+/// Bounded file to metadata store that evicts by `EvictionPolicy` once full. Entries remember
+/// the fingerprint of their file, so lookups of files modified or deleted since miss.
 #[derive(Deserialize, Serialize)]
+#[serde(from = "StoredCache")]
 pub struct PersistentCache {
-    cache_lookup: HashMap<String, usize>,
-    last_inserted_index: usize,
-    #[serde_as(as = "[Option<_>; MAX_CACHE_SIZE]")]
-    entries: [Option<MediaMeta>; MAX_CACHE_SIZE],
+    capacity: usize,
+    policy: EvictionPolicy,
+    /// logical clock, ticks on every access so recency survives restarts
+    clock: u64,
+    stats: CacheStats,
+    cache_lookup: HashMap<PathBuf, usize>,
+    entries: Vec<CacheEntry>,
+    /// rank and index of every entry, so the policy's victim is always the first one
+    #[serde(skip)]
+    order: BTreeSet<(Rank, usize)>,
 }
 
-#[must_use]
-fn gen_empty_cache_entries() -> [Option<MediaMeta>; MAX_CACHE_SIZE] {
-    use std::convert::TryInto;
+/// `PersistentCache` as stored, the eviction order is rebuilt from the entries
+#[derive(Deserialize)]
+struct StoredCache {
+    capacity: usize,
+    policy: EvictionPolicy,
+    clock: u64,
+    stats: CacheStats,
+    cache_lookup: HashMap<PathBuf, usize>,
+    entries: Vec<CacheEntry>,
+}
 
-    let mut cache_on_heap = Vec::with_capacity(MAX_CACHE_SIZE);
-    for _ in 0..MAX_CACHE_SIZE {
-        cache_on_heap.push(None);
+impl From<StoredCache> for PersistentCache {
+    fn from(stored: StoredCache) -> Self {
+        let mut cache = Self {
+            capacity: stored.capacity,
+            policy: stored.policy,
+            clock: stored.clock,
+            stats: stored.stats,
+            cache_lookup: stored.cache_lookup,
+            entries: stored.entries,
+            order: BTreeSet::new(),
+        };
+        cache.reorder();
+        cache
     }
+}
 
-    cache_on_heap
-        .try_into()
-        .unwrap_or_else(|_| panic!("Cache size must be {}", MAX_CACHE_SIZE))
+/// Canonical path of a file along with its fingerprint, what cache entries are found by
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheKey {
+    path: PathBuf,
+    fingerprint: Fingerprint,
+}
+
+impl CacheKey {
+    /// Key of the file at `path` in its current state, `None` once it is gone
+    pub fn of(path: &Path) -> Option<Self> {
+        let path = path.canonicalize().ok()?;
+        let fingerprint = Fingerprint::of(&path).ok()?;
+        Some(Self { path, fingerprint })
+    }
+
+    /// Canonical path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 }
 
 impl PersistentCache {
     pub fn new() -> Self {
+        Self::with_capacity(MAX_CACHE_SIZE, EvictionPolicy::default())
+    }
+
+    pub fn with_capacity(capacity: usize, policy: EvictionPolicy) -> Self {
         Self {
+            capacity: capacity.max(1),
+            policy,
+            clock: 0,
+            stats: CacheStats::default(),
             cache_lookup: HashMap::new(),
-            last_inserted_index: 0,
-            entries: gen_empty_cache_entries(),
+            entries: Vec::new(),
+            order: BTreeSet::new(),
         }
     }
 
-    pub fn get(&self, query: &str) -> Option<&MediaMeta> {
-        let lookup_index = self.cache_lookup.get(query)?;
-        self.entries.get(*lookup_index).map(|e| e.as_ref())?
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    /// Applies a new capacity and policy, evicting entries that no longer fit.
+    /// Returns how many were evicted.
+    pub fn configure(&mut self, capacity: usize, policy: EvictionPolicy) -> usize {
+        self.capacity = capacity.max(1);
+        if policy != self.policy {
+            self.policy = policy;
+            self.reorder();
+        }
+
+        let mut evicted = 0;
+        while self.entries.len() > self.capacity {
+            self.evict();
+            evicted += 1;
+        }
+        evicted
     }
 
-    fn next_insertion_index(&mut self) -> Result<()> {
-        self.last_inserted_index = self
+    /// Ranks every entry anew, after the policy or many entries changed
+    fn reorder(&mut self) {
+        let policy = self.policy;
+        self.order = self
             .entries
             .iter()
-            .position(|i| i.is_none())
-            .ok_or_else(|| anyhow!("Cache of size {} is full", MAX_CACHE_SIZE))?;
-        Ok(())
+            .enumerate()
+            .map(|(index, entry)| (rank(policy, entry), index))
+            .collect();
     }
 
-    pub fn insert(&mut self, key: &str, entry: MediaMeta) -> Result<()> {
-        if !self.cache_lookup.contains_key(key) {
-            self.entries
-                .get_mut(self.last_inserted_index)
-                .map(|e| e.replace(entry));
-            Ok(())
-        } else {
-            bail!("{} is an existing key", key)
-        }
+    fn touch(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        self.order.remove(&(rank(self.policy, entry), index));
+
+        self.clock += 1;
+        entry.last_access = self.clock;
+        entry.accesses += 1;
+        self.order.insert((rank(self.policy, entry), index));
     }
 
-    pub fn remove(&mut self, key: &str) -> Option<MediaMeta> {
-        let index = self.cache_lookup.get(key)?;
-        if *index < MAX_CACHE_SIZE {
-            self.entries[*index].take()
-        } else {
-            None
+    /// Index of the entry cached from the file in the state `key` was taken in
+    fn find(&self, key: &CacheKey) -> Option<usize> {
+        let index = *self.cache_lookup.get(&key.path)?;
+        Some(index).filter(|&index| self.entries[index].fingerprint == key.fingerprint)
+    }
+
+    /// Like `find`, counting the lookup as a hit or miss
+    fn lookup(&mut self, key: Option<&CacheKey>) -> Option<usize> {
+        let index = key.and_then(|key| self.find(key));
+        match index {
+            Some(index) => {
+                self.touch(index);
+                self.stats.hits += 1;
+            }
+            None => self.stats.misses += 1,
         }
+        index
     }
 
-    pub fn retain(&mut self, functor: fn(&str, Option<&MediaMeta>) -> bool) {
-        let mut keys_to_remove = vec![];
+    /// Cached metadata of `path`, a miss if the file changed or vanished since it was cached
+    pub fn get(&mut self, path: &Path) -> Option<&MediaMeta> {
+        let index = self.lookup(CacheKey::of(path).as_ref())?;
+        Some(&self.entries[index].meta)
+    }
 
-        for (key, index) in self.cache_lookup.iter_mut() {
-            let maybe_meta = self.entries.get(*index).map(|opt| opt.as_ref()).flatten();
-            let must_remove = functor(key, maybe_meta);
+    /// Like `get`, for a key taken beforehand
+    pub fn get_keyed(&mut self, key: &CacheKey) -> Option<&MediaMeta> {
+        let index = self.lookup(Some(key))?;
+        Some(&self.entries[index].meta)
+    }
+
+    /// Like `get_keyed`, without counting as an access. Lets a cache shared between threads
+    /// be read, with the lookups replayed through `get_keyed` once it can be borrowed mutably.
+    pub fn peek(&self, key: &CacheKey) -> Option<&MediaMeta> {
+        Some(&self.entries[self.find(key)?].meta)
+    }
 
-            if must_remove {
-                if let Some(entry) = self.entries.get_mut(*index) {
-                    *entry = None;
+    /// Like `get`, analyzing and caching the file again on a miss. The file is fingerprinted
+    /// before `analyze` reads it, so changes made meanwhile leave the new entry stale.
+    pub fn get_or_insert_with<F>(&mut self, path: &Path, analyze: F) -> Result<Option<&MediaMeta>>
+    where
+        F: FnOnce(&Path) -> Result<Option<MediaMeta>>,
+    {
+        let key = CacheKey::of(path);
+        let index = match self.lookup(key.as_ref()) {
+            Some(index) => index,
+            None => {
+                let key = key.ok_or_else(|| anyhow!("{} can't be cached", path.display()))?;
+                match analyze(path)? {
+                    Some(meta) => self.insert_fingerprinted(key, meta),
+                    None => return Ok(None),
                 }
-                keys_to_remove.push(key.clone());
             }
+        };
+        Ok(Some(&self.entries[index].meta))
+    }
+
+    /// Index of the entry the policy gives up first
+    fn victim(&self) -> Option<usize> {
+        self.order.iter().next().map(|&(_, index)| index)
+    }
+
+    fn evict(&mut self) {
+        if let Some(index) = self.victim() {
+            self.remove_at(index);
+            self.stats.evictions += 1;
+        }
+    }
+
+    /// Takes the entry out by swapping the last one into its slot
+    fn remove_at(&mut self, index: usize) -> CacheEntry {
+        let last = self.entries.len() - 1;
+        let entry = self.entries.swap_remove(index);
+        self.cache_lookup.remove(&entry.key);
+        self.order.remove(&(rank(self.policy, &entry), index));
+
+        if let Some(moved) = self.entries.get(index) {
+            self.cache_lookup.insert(moved.key.clone(), index);
+            let moved_rank = rank(self.policy, moved);
+            self.order.remove(&(moved_rank, last));
+            self.order.insert((moved_rank, index));
+        }
+        entry
+    }
+
+    /// Adds an entry for a new key, evicting one first if the cache is full. Returns its index.
+    fn push(&mut self, entry: CacheEntry) -> usize {
+        if self.entries.len() >= self.capacity {
+            self.evict();
         }
 
-        for key in keys_to_remove {
-            self.cache_lookup.remove(key.as_str());
+        let index = self.entries.len();
+        self.cache_lookup.insert(entry.key.clone(), index);
+        self.order.insert((rank(self.policy, &entry), index));
+        self.entries.push(entry);
+        index
+    }
+
+    /// Caches `meta` as read from the file at `path` in its current state, replacing an
+    /// outdated entry or evicting one first if the cache is full. Prefer `get_or_insert_with`
+    /// when reading the file, which fingerprints it before rather than after.
+    pub fn insert(&mut self, path: &Path, meta: MediaMeta) -> Result<()> {
+        let key =
+            CacheKey::of(path).ok_or_else(|| anyhow!("{} can't be cached", path.display()))?;
+        self.insert_fingerprinted(key, meta);
+        Ok(())
+    }
+
+    /// Caches `meta` as read from the file in the state `key` was taken in, which should be
+    /// before reading it
+    pub fn insert_keyed(&mut self, key: CacheKey, meta: MediaMeta) {
+        self.insert_fingerprinted(key, meta);
+    }
+
+    /// Like `insert_keyed`, returning the index of the entry
+    fn insert_fingerprinted(&mut self, key: CacheKey, meta: MediaMeta) -> usize {
+        let CacheKey { path, fingerprint } = key;
+        let index = match self.cache_lookup.get(&path) {
+            Some(&index) => {
+                let entry = &mut self.entries[index];
+                entry.fingerprint = fingerprint;
+                entry.meta = meta;
+                index
+            }
+            None => self.push(CacheEntry {
+                key: path,
+                fingerprint,
+                meta,
+                last_access: 0,
+                accesses: 0,
+            }),
+        };
+
+        self.touch(index);
+        self.stats.insertions += 1;
+        index
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<MediaMeta> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let index = *self.cache_lookup.get(&key)?;
+        Some(self.remove_at(index).meta)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over every entry along with its key, without counting as accesses
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &MediaMeta)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.key.as_path(), &entry.meta))
+    }
+
+    fn keep_where(&mut self, mut keep: impl FnMut(&CacheEntry) -> bool) {
+        let entries = std::mem::take(&mut self.entries);
+        self.cache_lookup.clear();
+
+        for entry in entries {
+            if keep(&entry) {
+                self.cache_lookup
+                    .insert(entry.key.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
         }
+        self.reorder();
+    }
+
+    /// Removes every entry `functor` returns true for
+    pub fn retain(&mut self, functor: fn(&Path, Option<&MediaMeta>) -> bool) {
+        self.keep_where(|entry| !functor(&entry.key, Some(&entry.meta)));
     }
 
+    /// Looks up every query, in order, skipping the ones not cached
     #[must_use]
-    pub fn batch_query<P: AsRef<str>>(&self, queries: &[P]) -> Option<Vec<&MediaMeta>> {
-        let res_indexes: Vec<usize> = queries
+    pub fn batch_query<P: AsRef<Path>>(&mut self, queries: &[P]) -> Option<Vec<&MediaMeta>> {
+        let indices: Vec<usize> = queries
             .iter()
-            .filter_map(|q| self.cache_lookup.get(q.as_ref()).copied())
+            .filter_map(|query| self.lookup(CacheKey::of(query.as_ref()).as_ref()))
             .collect();
 
-        if res_indexes.is_empty() {
+        if indices.is_empty() {
             return None;
         }
+        let entries = &self.entries;
+        Some(
+            indices
+                .into_iter()
+                .map(|index| &entries[index].meta)
+                .collect(),
+        )
+    }
 
-        let batch_results = self
-            .entries
-            .iter()
-            .enumerate()
-            .filter_map(|(ix, meta)| res_indexes.contains(&ix).opt_and(meta.as_ref()))
-            .collect();
-        Some(batch_results)
+    /// Drops the entries of files modified or deleted since they were cached, returning how many
+    pub fn prune_stale(&mut self) -> usize {
+        let before = self.entries.len();
+        self.keep_where(|entry| {
+            CacheKey::of(&entry.key).map(|key| key.fingerprint) == Some(entry.fingerprint)
+        });
+        before - self.entries.len()
+    }
+
+    /// Every entry as json, along with the fingerprints and access counts `import_json` restores
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        let export = CacheExport {
+            format_version: FORMAT_VERSION,
+            entries: &self.entries,
+        };
+
+        if pretty {
+            Ok(serde_json::to_string_pretty(&export)?)
+        } else {
+            Ok(serde_json::to_string(&export)?)
+        }
+    }
+
+    /// Adds the entries of a cache exported by `to_json`, replacing those for the same files and
+    /// evicting as usual once full. Returns how many entries were read.
+    pub fn import_json(&mut self, json: &str) -> Result<usize> {
+        let export: CacheExport<Vec<CacheEntry>> = serde_json::from_str(json)?;
+        if export.format_version > FORMAT_VERSION {
+            bail!(
+                "Export format version {} was written by a newer buo, this one reads up to version {}",
+                export.format_version,
+                FORMAT_VERSION
+            );
+        }
+
+        let imported = export.entries.len();
+        for entry in export.entries {
+            // keeps imported recency comparable with accesses from here on
+            self.clock = self.clock.max(entry.last_access);
+            match self.cache_lookup.get(&entry.key) {
+                Some(&index) => {
+                    self.order
+                        .remove(&(rank(self.policy, &self.entries[index]), index));
+                    self.order.insert((rank(self.policy, &entry), index));
+                    self.entries[index] = entry;
+                }
+                None => {
+                    self.push(entry);
+                }
+            }
+        }
+        Ok(imported)
     }
 }
 
+/// Layout of `PersistentCache::to_json`
+#[derive(Deserialize, Serialize)]
+struct CacheExport<E> {
+    format_version: u32,
+    entries: E,
+}
+
 impl Default for PersistentCache {
     fn default() -> Self {
         Self::new()
     }
 }
 
-use std::fs::{read, write};
-pub fn commit_cache_to_path(path: &Path, cache: PersistentCache) -> Result<()> {
-    let serialized: Vec<u8> = bincode::serialize(&cache)?;
-    write(path, serialized)?;
+use super::{
+    format::{decode, encode, FORMAT_VERSION},
+    lock::CacheLock,
+};
+use std::{fs::read, io::Write};
+use tempfile::NamedTempFile;
+
+/// Writes a temporary file next to `path` and renames it over the cache, so neither readers
+/// nor a crash midway ever see a partial cache. Callers hold the exclusive lock.
+fn write_cache(path: &Path, cache: &PersistentCache) -> Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut file = NamedTempFile::new_in(dir)?;
+    // temporary files are private, a replaced cache keeps its permissions
+    if let Ok(metadata) = std::fs::metadata(path) {
+        file.as_file().set_permissions(metadata.permissions())?;
+    }
+    file.write_all(&encode(cache)?)?;
+    file.as_file().sync_all()?;
+    file.persist(path)?;
     Ok(())
 }
 
-use bincode::ErrorKind as BinErr;
-use std::io::ErrorKind as IoErr;
+/// Replaces the cache at `path` with `cache`. Changes to a cache read beforehand belong in
+/// `update_cache` instead, which holds the lock from the read to the commit.
+pub fn commit_cache_to_path(path: &Path, cache: PersistentCache) -> Result<()> {
+    let _lock = CacheLock::exclusive(path)?;
+    write_cache(path, &cache)
+}
+
+/// The cache at `path` and the format version it's stored in, `None` if missing or empty
+fn read_cache(path: &Path) -> Result<Option<(PersistentCache, u32)>> {
+    let byte_contents = match read(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        res => res?,
+    };
+    if byte_contents.is_empty() {
+        return Ok(None);
+    }
+
+    let decoded = decode(&byte_contents).map_err(|e| {
+        anyhow!(
+            "Unable to read cache {}: {}, `buo cache init` replaces it with an empty one",
+            path.display(),
+            e
+        )
+    })?;
+    Ok(Some(decoded))
+}
+
+/// Reads the cache at `path`, applies the capacity and eviction policy of `config` and then
+/// `update`, and commits the result, all under the exclusive lock so that no other process
+/// commits in between. A missing cache starts out empty.
+pub fn update_cache<T, F>(path: &Path, config: &CacheConfig, update: F) -> Result<T>
+where
+    F: FnOnce(&mut PersistentCache) -> T,
+{
+    let _lock = CacheLock::exclusive(path)?;
+    let mut cache = match read_cache(path)? {
+        Some((cache, _)) => cache,
+        None => config.new_cache(),
+    };
+    cache.configure(config.capacity, config.eviction);
 
-fn init_cache(path: &Path) -> Result<PersistentCache> {
-    let dfl_cache = PersistentCache::new();
-    let serialized_cache: Vec<u8> = bincode::serialize(&dfl_cache)?;
-    write(path, serialized_cache)?;
-    Ok(dfl_cache)
+    let updated = update(&mut cache);
+    write_cache(path, &cache)?;
+    Ok(updated)
 }
 
-fn is_init_candidate(err: &BinErr) -> bool {
-    use IoErr::{AlreadyExists, InvalidData, InvalidInput, NotFound, PermissionDenied};
-    match err {
-        BinErr::Io(e)
-            if matches!(
-                e.kind(),
-                NotFound | PermissionDenied | AlreadyExists | InvalidInput | InvalidData
-            ) =>
-        {
-            true
+/// Reads the cache at `path`, migrating older formats in place. Only a missing or empty file
+/// is initialized, an unreadable one is an error rather than overwritten.
+pub fn retrieve_or_init_cache(path: &Path) -> Result<PersistentCache> {
+    retrieve_or_init_with(path, PersistentCache::new)
+}
+
+/// Like `retrieve_or_init_cache`, sized and evicting as `config` says. A cache stored with other
+/// settings only changes in memory, until the next `update_cache` commits it.
+pub fn retrieve_configured_cache(path: &Path, config: &CacheConfig) -> Result<PersistentCache> {
+    let mut cache = retrieve_or_init_with(path, || config.new_cache())?;
+    cache.configure(config.capacity, config.eviction);
+    Ok(cache)
+}
+
+fn retrieve_or_init_with(
+    path: &Path,
+    init: impl FnOnce() -> PersistentCache,
+) -> Result<PersistentCache> {
+    {
+        let _lock = CacheLock::shared(path)?;
+        if let Some((cache, FORMAT_VERSION)) = read_cache(path)? {
+            return Ok(cache);
         }
-        _ => false,
     }
+
+    // another process may have initialized or migrated it before the exclusive lock was ours
+    let _lock = CacheLock::exclusive(path)?;
+    let cache = match read_cache(path)? {
+        Some((cache, FORMAT_VERSION)) => return Ok(cache),
+        Some((cache, _)) => cache,
+        None => init(),
+    };
+    write_cache(path, &cache)?;
+    Ok(cache)
 }
 
-pub fn retrieve_or_init_cache(path: &Path) -> Result<PersistentCache> {
-    let byte_contents = read(path)?;
-    let deserialized: Result<PersistentCache, _> = bincode::deserialize(&byte_contents);
+#[test]
+fn evicts_by_policy() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+    let file = |name: &str| -> Result<(PathBuf, MediaMeta)> {
+        let path = dir.join(name);
+        std::fs::write(&path, name)?;
+        let meta = MediaMeta {
+            file_name: name.to_owned(),
+            ..Default::default()
+        };
+        Ok((path, meta))
+    };
+    let ((a, a_meta), (b, b_meta), (c, c_meta)) = (file("a")?, file("b")?, file("c")?);
+
+    let mut lru = PersistentCache::with_capacity(2, EvictionPolicy::Lru);
+    lru.insert(&a, a_meta.clone())?;
+    lru.insert(&b, b_meta.clone())?;
+    assert!(lru.get(&a).is_some());
+    lru.insert(&c, c_meta.clone())?;
+    assert!(lru.get(&b).is_none());
+    assert_eq!(lru.batch_query(&[&a, &c]).map(|hits| hits.len()), Some(2));
+
+    let mut lfu = PersistentCache::with_capacity(2, EvictionPolicy::Lfu);
+    lfu.insert(&a, a_meta)?;
+    lfu.insert(&b, b_meta)?;
+    for _ in 0..3 {
+        lfu.get(&b);
+    }
+    lfu.get(&a);
+    lfu.insert(&c, c_meta)?;
+    assert!(lfu.get(&a).is_none());
+    assert_eq!(lfu.configure(1, EvictionPolicy::Lfu), 1);
+    assert_eq!(
+        lfu.entries().map(|(key, _)| key).collect::<Vec<_>>(),
+        vec![b.canonicalize()?]
+    );
+
+    let stats = lru.stats();
+    assert_eq!((stats.insertions(), stats.evictions()), (3, 1));
+    assert_eq!((stats.hits(), stats.misses()), (3, 1));
 
-    match deserialized {
-        Err(e) if is_init_candidate(&*e) => init_cache(path),
-        Err(e) => bail!(e),
-        Ok(res) => Ok(res),
+    // switching policies ranks the entries anew, `a` was accessed most but least recently
+    assert_eq!(lru.configure(1, EvictionPolicy::Lfu), 1);
+    assert!(lru.get(&a).is_some());
+    Ok(())
+}
+
+#[test]
+fn misses_changed_files() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+    let (song, gone) = (dir.join("song.mp3"), dir.join("gone.mp3"));
+    std::fs::write(&song, "buo")?;
+    std::fs::write(&gone, "buo")?;
+
+    let mut cache = PersistentCache::new();
+    let title = |title: &str| MediaMeta {
+        title: Some(title.to_owned()),
+        ..Default::default()
+    };
+    cache.insert(&song, title("old"))?;
+    cache.insert(&gone, title("gone"))?;
+    // relative and absolute spellings of a path share an entry
+    assert!(cache.get(&dir.join(".").join("song.mp3")).is_some());
+
+    std::fs::write(&song, "buo buo")?;
+    std::fs::remove_file(&gone)?;
+    assert!(cache.get(&song).is_none());
+    assert!(cache.get(&gone).is_none());
+
+    let refreshed = cache.get_or_insert_with(&song, |_| Ok(Some(title("new"))))?;
+    assert_eq!(
+        refreshed.and_then(|meta| meta.title.as_deref()),
+        Some("new")
+    );
+    assert_eq!(cache.prune_stale(), 1);
+    assert_eq!(cache.len(), 1);
+
+    // a file changed while it's analyzed stays a miss
+    std::fs::write(&song, "buo buo buo")?;
+    cache.get_or_insert_with(&song, |path| {
+        std::fs::write(path, "buo buo buo buo")?;
+        Ok(Some(title("racy")))
+    })?;
+    assert!(cache.get(&song).is_none());
+    Ok(())
+}
+
+#[test]
+fn roundtrips_json() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+    let (a, b) = (dir.join("a.mp3"), dir.join("b.mp3"));
+    std::fs::write(&a, "buo")?;
+    std::fs::write(&b, "buo")?;
+
+    let mut cache = PersistentCache::new();
+    cache.insert(&a, MediaMeta::default())?;
+    cache.insert(&b, MediaMeta::default())?;
+    cache.get(&b);
+    let json = cache.to_json(false)?;
+
+    let mut imported = PersistentCache::with_capacity(1, EvictionPolicy::Lfu);
+    assert_eq!(imported.import_json(&json)?, 2);
+    // the more accessed entry outlives the other
+    assert!(imported.get(&b).is_some());
+    assert_eq!(imported.len(), 1);
+
+    let newer = json.replacen(
+        &format!("\"format_version\":{}", FORMAT_VERSION),
+        "\"format_version\":999",
+        1,
+    );
+    assert!(imported.import_json(&newer).is_err());
+    Ok(())
+}
+
+#[test]
+fn applies_configured_capacity() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let cache_path = tmp.path().join("buo.cache");
+    let config = |capacity| CacheConfig {
+        capacity,
+        ..CacheConfig::default()
+    };
+
+    for name in &["a", "b", "c"] {
+        let file = tmp.path().join(name);
+        std::fs::write(&file, name)?;
+        update_cache(&cache_path, &config(3), |cache| {
+            cache.insert(&file, MediaMeta::default())
+        })??;
     }
+    let read = retrieve_configured_cache(&cache_path, &config(1))?;
+    assert_eq!((read.len(), read.capacity()), (1, 1));
+
+    update_cache(&cache_path, &config(2), |_| ())?;
+    let updated = retrieve_or_init_cache(&cache_path)?;
+    assert_eq!((updated.len(), updated.capacity()), (2, 2));
+    Ok(())
 }
@@ -0,0 +1,109 @@
+use crate::prelude::*;
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+/// Advisory lock on the cache at a path, held until dropped. Readers share it, a writer holds it
+/// alone. The lock lives on a sidecar file, since commits replace the cache file itself.
+pub struct CacheLock {
+    _file: File,
+}
+
+fn lock_path(cache_path: &Path) -> PathBuf {
+    let mut name = cache_path
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    name.push(".lock");
+    cache_path.with_file_name(name)
+}
+
+impl CacheLock {
+    /// Blocks until readers of the cache at `cache_path` may proceed
+    pub fn shared(cache_path: &Path) -> Result<Self> {
+        Self::acquire(cache_path, false)
+    }
+
+    /// Blocks until no other process reads or writes the cache at `cache_path`
+    pub fn exclusive(cache_path: &Path) -> Result<Self> {
+        Self::acquire(cache_path, true)
+    }
+
+    fn acquire(cache_path: &Path, exclusive: bool) -> Result<Self> {
+        let path = lock_path(cache_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| anyhow!("Unable to open {}: {}", path.display(), e))?;
+        flock(&file, exclusive).map_err(|e| anyhow!("Unable to lock {}: {}", path.display(), e))?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Closing the file releases the lock, so there is no explicit unlock
+#[cfg(unix)]
+fn flock(file: &File, exclusive: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Without `flock` only the atomic commits protect the cache
+#[cfg(not(unix))]
+fn flock(_file: &File, _exclusive: bool) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[test]
+fn serializes_concurrent_updates() -> Result<()> {
+    use super::{update_cache, PersistentCache};
+    use crate::util::config::CacheConfig;
+
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().to_path_buf();
+    let cache_path = dir.join("buo.cache");
+
+    let writers: Vec<_> = (0..4)
+        .map(|writer| {
+            let (dir, cache_path) = (dir.clone(), cache_path.clone());
+            std::thread::spawn(move || -> Result<()> {
+                for n in 0..10 {
+                    let file = dir.join(format!("{}-{}.mp3", writer, n));
+                    std::fs::write(&file, "buo")?;
+                    update_cache(
+                        &cache_path,
+                        &CacheConfig::default(),
+                        |cache: &mut PersistentCache| cache.insert(&file, MediaMeta::default()),
+                    )??;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().map_err(|_| anyhow!("writer panicked"))??;
+    }
+
+    // no update was lost, and no temporary files remain
+    let cache = super::retrieve_or_init_cache(&cache_path)?;
+    assert_eq!(cache.len(), 40);
+    assert_eq!(std::fs::read_dir(&dir)?.count(), 42);
+    Ok(())
+}
@@ -1,18 +1,47 @@
-use crate::prelude::*;
+use crate::{prelude::*, util::fingerprint::Fingerprint};
+use std::path::{Path, PathBuf};
 use tinyvec::{array_vec, ArrayVec};
 
 const MIN_CACHE_SIZE: usize = 64;
 
+#[derive(Default, Deserialize, Serialize)]
+struct LiveEntry {
+    /// canonical path of the file `meta` was read from
+    path: PathBuf,
+    /// `None` if the file was already gone when cached
+    fingerprint: Option<Fingerprint>,
+    meta: MediaMeta,
+}
+
+impl LiveEntry {
+    fn new(meta: MediaMeta) -> Self {
+        let path = meta
+            .file_path
+            .canonicalize()
+            .unwrap_or_else(|_| meta.file_path.clone());
+        Self {
+            fingerprint: Fingerprint::of(&path).ok(),
+            path,
+            meta,
+        }
+    }
+
+    /// Whether the file is unchanged since it was cached
+    fn is_fresh(&self) -> bool {
+        self.fingerprint.is_some() && Fingerprint::of(&self.path).ok() == self.fingerprint
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct LiveCache {
-    entries: ArrayVec<[MediaMeta; MIN_CACHE_SIZE]>,
+    entries: ArrayVec<[LiveEntry; MIN_CACHE_SIZE]>,
     last_inserted_index: usize,
 }
 
 impl Default for LiveCache {
     fn default() -> Self {
         Self {
-            entries: array_vec!([MediaMeta; MIN_CACHE_SIZE]),
+            entries: array_vec!([LiveEntry; MIN_CACHE_SIZE]),
             last_inserted_index: 0,
         }
     }
@@ -28,7 +57,7 @@ impl LiveCache {
     }
 
     pub fn contains(&self, meta: &MediaMeta) -> bool {
-        self.entries.iter().any(|e| e == meta)
+        self.entries.iter().any(|e| e.meta == *meta)
     }
 
     pub fn has_capacity(&self) -> bool {
@@ -43,23 +72,42 @@ impl LiveCache {
         }
     }
 
-    pub fn find<P>(&self, fun: P) -> Option<&MediaMeta>
+    /// First cached metadata matching `fun` whose file is unchanged since it was cached
+    pub fn find<P>(&self, mut fun: P) -> Option<&MediaMeta>
     where
         P: FnMut(&&MediaMeta) -> bool,
     {
-        self.entries.iter().find(fun)
+        self.entries
+            .iter()
+            .filter(|entry| fun(&&entry.meta))
+            .find(|entry| entry.is_fresh())
+            .map(|entry| &entry.meta)
+    }
+
+    /// Cached metadata of the file at `path`, a miss if it changed or vanished since
+    pub fn get(&self, path: &Path) -> Option<&MediaMeta> {
+        let path = path.canonicalize().ok()?;
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path)
+            .filter(|entry| entry.is_fresh())
+            .map(|entry| &entry.meta)
     }
 
     pub fn entries(&self) -> impl Iterator<Item = &MediaMeta> {
-        self.entries.iter()
+        self.entries.iter().map(|entry| &entry.meta)
     }
 
+    /// Caches `meta`, refreshing the entry of the same file if there is one
     pub fn insert(&mut self, meta: MediaMeta) {
-        if self.has_capacity() {
-            self.entries.push(meta);
+        let entry = LiveEntry::new(meta);
+        if let Some(existing) = self.entries.iter_mut().find(|e| e.path == entry.path) {
+            *existing = entry;
+        } else if self.has_capacity() {
+            self.entries.push(entry);
         } else {
             self.entries.remove(self.last_inserted_index);
-            self.entries.insert(self.last_inserted_index, meta);
+            self.entries.insert(self.last_inserted_index, entry);
 
             self.increment()
         }
@@ -0,0 +1,328 @@
+use crate::{
+    prelude::*,
+    util::{
+        cache::{default_cache_path, EvictionPolicy, PersistentCache, MAX_CACHE_SIZE},
+        index::default_index_path,
+        ranker::default_ranker_path,
+        walk::Ignorer,
+        web::BookmarkSource,
+    },
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+use strum::IntoEnumIterator;
+
+const CONFIG_DIR: &str = "buo";
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputKind {
+    Text,
+    Json,
+    Array,
+    Pretty,
+    Csv,
+    Tsv,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// format used when no output flag is passed
+    pub format: Option<OutputKind>,
+    /// template used when no output flag is passed, takes precedence over `format`
+    pub template: Option<String>,
+    /// print a header row for csv and tsv output
+    pub header: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            format: None,
+            template: None,
+            header: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub path: Option<PathBuf>,
+    /// entries kept before the eviction policy makes room for new ones
+    pub capacity: usize,
+    pub eviction: EvictionPolicy,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            capacity: MAX_CACHE_SIZE,
+            eviction: EvictionPolicy::default(),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// An empty cache sized and evicting as configured
+    pub fn new_cache(&self) -> PersistentCache {
+        PersistentCache::with_capacity(self.capacity, self.eviction)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// sqlite database used by `index` and `search`
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    /// directories watched when none are passed to `watch`
+    pub roots: Vec<PathBuf>,
+    /// quiet period after the last change before the index is updated
+    pub debounce_ms: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            debounce_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// record files picked in `find -i` as visits
+    pub record: bool,
+    /// days after which a visit counts half as much towards frecency
+    pub half_life_days: f64,
+    /// how much frecency adds to search relevance, which is 1 for the best match
+    pub weight: f64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            record: true,
+            half_life_days: 14.0,
+            weight: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankerConfig {
+    /// learn from `find -i` picks and rank with the model once it has enough feedback
+    pub enabled: bool,
+    pub path: Option<PathBuf>,
+}
+
+impl Default for RankerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyzerConfig {
+    pub audio: bool,
+    pub video: bool,
+    pub code: bool,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            video: true,
+            code: true,
+        }
+    }
+}
+
+impl AnalyzerConfig {
+    /// Mirrors the categories analyzers are registered for in `EXT_FNS`
+    pub fn allows(&self, file_ext: FileExt) -> bool {
+        if file_ext.is_audio() {
+            self.audio
+        } else if file_ext.is_video() {
+            self.video
+        } else if file_ext.is_dev() {
+            self.code
+        } else {
+            false
+        }
+    }
+
+    pub fn allows_path(&self, path: &Path) -> bool {
+        matches!(get_file_ext(path), Some(ext) if self.allows(FileExt::from(ext)))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookmarkConfig {
+    /// browsers read when none is passed on the command line
+    pub sources: Vec<BookmarkSource>,
+    /// profile directory name, defaults to the `default-release` profile
+    pub firefox_profile: Option<String>,
+    /// profile directory name, defaults to `Default`
+    pub chromium_profile: Option<String>,
+}
+
+impl Default for BookmarkConfig {
+    fn default() -> Self {
+        Self {
+            sources: BookmarkSource::iter().collect(),
+            firefox_profile: None,
+            chromium_profile: None,
+        }
+    }
+}
+
+impl BookmarkConfig {
+    pub fn profile_for(&self, source: BookmarkSource) -> Option<&str> {
+        match source {
+            BookmarkSource::Firefox => self.firefox_profile.as_deref(),
+            _ => self.chromium_profile.as_deref(),
+        }
+    }
+}
+
+/// Settings read from `~/.config/buo/config.toml`, every field is optional
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuoConfig {
+    /// glob patterns for files and directories skipped by directory walks
+    pub ignore: Vec<String>,
+    /// skip what `.gitignore` and `.buoignore` files exclude, and `.git` directories
+    pub ignore_files: bool,
+    pub output: OutputConfig,
+    pub cache: CacheConfig,
+    pub index: IndexConfig,
+    pub watch: WatchConfig,
+    pub history: HistoryConfig,
+    pub ranker: RankerConfig,
+    pub analyzers: AnalyzerConfig,
+    pub bookmarks: BookmarkConfig,
+}
+
+impl Default for BuoConfig {
+    fn default() -> Self {
+        Self {
+            ignore: Vec::new(),
+            ignore_files: true,
+            output: OutputConfig::default(),
+            cache: CacheConfig::default(),
+            index: IndexConfig::default(),
+            watch: WatchConfig::default(),
+            history: HistoryConfig::default(),
+            ranker: RankerConfig::default(),
+            analyzers: AnalyzerConfig::default(),
+            bookmarks: BookmarkConfig::default(),
+        }
+    }
+}
+
+impl BuoConfig {
+    pub fn default_path() -> Result<PathBuf> {
+        let mut config_path =
+            dirs::config_dir().ok_or_else(|| anyhow!("Unable to locate config dir"))?;
+        config_path.push(CONFIG_DIR);
+        config_path.push(CONFIG_FILE);
+        Ok(config_path)
+    }
+
+    /// Loads the config at `path`, or the default location if none is given.
+    /// Only a missing default config falls back to the defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let config_path = match path {
+            Some(path) => path.to_path_buf(),
+            None => Self::default_path()?,
+        };
+
+        if path.is_none() && !config_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = read_to_string(&config_path)
+            .map_err(|e| anyhow!("Unable to read {}: {}", config_path.display(), e))?;
+        let config: Self = toml::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid config {}: {}", config_path.display(), e))?;
+        if config.cache.capacity == 0 {
+            bail!(
+                "Invalid config {}: cache.capacity must be at least 1",
+                config_path.display()
+            );
+        }
+        let half_life_days = config.history.half_life_days;
+        if half_life_days.is_nan() || half_life_days <= 0.0 {
+            bail!(
+                "Invalid config {}: history.half_life_days must be a positive number",
+                config_path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    pub fn cache_path(&self) -> Result<PathBuf> {
+        match self.cache.path {
+            Some(ref path) => Ok(path.clone()),
+            None => default_cache_path(),
+        }
+    }
+
+    pub fn index_path(&self) -> Result<PathBuf> {
+        match self.index.path {
+            Some(ref path) => Ok(path.clone()),
+            None => default_index_path(),
+        }
+    }
+
+    pub fn ranker_path(&self) -> Result<PathBuf> {
+        match self.ranker.path {
+            Some(ref path) => Ok(path.clone()),
+            None => default_ranker_path(),
+        }
+    }
+
+    pub fn ignore_globs(&self) -> Result<GlobSet> {
+        let mut globs = GlobSetBuilder::new();
+        for pattern in &self.ignore {
+            globs.add(Glob::new(pattern)?);
+        }
+        Ok(globs.build()?)
+    }
+
+    /// What every directory walk skips
+    pub fn ignorer(&self) -> Result<Ignorer> {
+        Ok(Ignorer::new(self.ignore_globs()?, self.ignore_files))
+    }
+
+    /// Turns off every ignore rule, for `--no-ignore`
+    pub fn disable_ignores(&mut self) {
+        self.ignore.clear();
+        self.ignore_files = false;
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
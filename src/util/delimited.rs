@@ -0,0 +1,220 @@
+use crate::{
+    prelude::*,
+    util::{
+        dupes::DupeGroup,
+        index::SearchHit,
+        json_out::{ExportKind, ExportStatus, TargetIssue},
+        web::WebBookmark,
+    },
+};
+
+/// Column order shared by every csv/tsv record, so that mixed runs of
+/// files, directories and skipped targets load into a single table
+pub const DELIMITED_COLUMNS: &[&str] = &[
+    "type",
+    "status",
+    "path",
+    "file_name",
+    "title",
+    "author",
+    "duration",
+    "date",
+    "languages",
+    "loc",
+    "comments",
+    "disk_size",
+    "num_files",
+    "message",
+    "snippet",
+    "hash",
+    "reclaimable",
+];
+
+#[derive(Default)]
+pub struct DelimitedRow {
+    pub path: Option<String>,
+    pub file_name: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub duration: Option<String>,
+    pub date: Option<String>,
+    pub languages: Option<String>,
+    pub loc: Option<String>,
+    pub comments: Option<String>,
+    pub disk_size: Option<String>,
+    pub num_files: Option<String>,
+    pub message: Option<String>,
+    pub snippet: Option<String>,
+    pub hash: Option<String>,
+    pub reclaimable: Option<String>,
+}
+
+pub trait DelimitedRecord {
+    fn delimited_row(&self) -> DelimitedRow;
+}
+
+/// Quotes a field if it contains the delimiter, quotes or line breaks
+fn escape_field(field: &str, delimiter: char) -> String {
+    if field.contains(&[delimiter, '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn join_fields<'f>(fields: impl Iterator<Item = &'f str>, delimiter: char) -> String {
+    fields
+        .map(|field| escape_field(field, delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
+
+pub fn delimited_header(delimiter: char) -> String {
+    join_fields(DELIMITED_COLUMNS.iter().copied(), delimiter)
+}
+
+impl DelimitedRow {
+    pub fn to_line(&self, kind: &ExportKind, status: ExportStatus, delimiter: char) -> String {
+        let kind = kind.to_string();
+        let status = status.as_ref().to_owned();
+        let fields = [
+            Some(&kind),
+            Some(&status),
+            self.path.as_ref(),
+            self.file_name.as_ref(),
+            self.title.as_ref(),
+            self.author.as_ref(),
+            self.duration.as_ref(),
+            self.date.as_ref(),
+            self.languages.as_ref(),
+            self.loc.as_ref(),
+            self.comments.as_ref(),
+            self.disk_size.as_ref(),
+            self.num_files.as_ref(),
+            self.message.as_ref(),
+            self.snippet.as_ref(),
+            self.hash.as_ref(),
+            self.reclaimable.as_ref(),
+        ];
+
+        join_fields(
+            fields
+                .iter()
+                .map(|field| field.map(|f| f.as_str()).unwrap_or_default()),
+            delimiter,
+        )
+    }
+}
+
+impl DelimitedRecord for MediaMeta {
+    fn delimited_row(&self) -> DelimitedRow {
+        let (languages, loc, comments) = match self.stats {
+            Some(ref stats) => {
+                let languages = stats
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(";");
+                let loc: usize = stats.iter().filter_map(|s| s.loc).map(|n| n.get()).sum();
+                let comments: usize = stats
+                    .iter()
+                    .filter_map(|s| s.comments)
+                    .map(|n| n.get())
+                    .sum();
+                (
+                    Some(languages),
+                    Some(loc.to_string()),
+                    Some(comments.to_string()),
+                )
+            }
+            None => (None, None, None),
+        };
+
+        let date = self.date.as_ref().map(|date| match date {
+            DateKind::Chrono(date) => date.to_rfc3339(),
+            DateKind::Sym(year) => year.clone(),
+        });
+
+        DelimitedRow {
+            path: Some(self.file_path.to_string_lossy().to_string()),
+            file_name: Some(self.file_name.clone()),
+            title: self.title.clone(),
+            author: self.author.clone(),
+            duration: self.duration.map(|d| d.as_secs_f64().to_string()),
+            date,
+            languages,
+            loc,
+            comments,
+            ..Default::default()
+        }
+    }
+}
+
+impl DelimitedRecord for DirMeta {
+    fn delimited_row(&self) -> DelimitedRow {
+        DelimitedRow {
+            path: Some(self.path.to_string_lossy().to_string()),
+            disk_size: Some(self.disk_size.to_string()),
+            num_files: Some(self.num_files.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl DelimitedRecord for WebBookmark {
+    fn delimited_row(&self) -> DelimitedRow {
+        DelimitedRow {
+            path: Some(self.url.clone()),
+            title: self.name.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl DelimitedRecord for TargetIssue {
+    fn delimited_row(&self) -> DelimitedRow {
+        DelimitedRow {
+            path: Some(self.file_path.to_string_lossy().to_string()),
+            message: Some(self.message.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl DelimitedRecord for SearchHit {
+    fn delimited_row(&self) -> DelimitedRow {
+        DelimitedRow {
+            snippet: self.snippet.clone(),
+            ..self.meta.delimited_row()
+        }
+    }
+}
+
+impl DelimitedRecord for DupeGroup {
+    fn delimited_row(&self) -> DelimitedRow {
+        let paths = self
+            .paths
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        DelimitedRow {
+            path: Some(paths),
+            disk_size: Some(self.disk_size.to_string()),
+            num_files: Some(self.paths.len().to_string()),
+            hash: Some(self.hash.clone()),
+            reclaimable: Some(self.reclaimable.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[test]
+fn escapes_delimited_fields() {
+    assert_eq!(escape_field("plain", ','), "plain");
+    assert_eq!(escape_field("a,b", ','), "\"a,b\"");
+    assert_eq!(escape_field("a,b", '\t'), "a,b");
+    assert_eq!(escape_field("say \"hi\"", '\t'), "\"say \"\"hi\"\"\"");
+    assert_eq!(escape_field("two\nlines", ','), "\"two\nlines\"");
+}
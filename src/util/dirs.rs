@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{prelude::*, util::walk::Ignorer};
 use std::path::{Path, PathBuf};
 
 #[derive(Serialize)]
@@ -8,11 +8,15 @@ pub struct DirMeta {
     pub num_files: u64,
 }
 
+pub fn human_readable_bytes(bytes: u64) -> String {
+    byte_unit::Byte::from(bytes)
+        .get_appropriate_unit(false)
+        .to_string()
+}
+
 impl DirMeta {
     pub fn human_readable_disk_size(&self) -> String {
-        byte_unit::Byte::from(self.disk_size)
-            .get_appropriate_unit(false)
-            .to_string()
+        human_readable_bytes(self.disk_size)
     }
 }
 
@@ -39,12 +43,21 @@ struct DirWalker {
 
 use filesize::PathExt;
 use std::fs::read_dir;
-fn recurse_total_dir_size(dir_path: &Path, walk_results: &mut DirWalker) {
+fn recurse_total_dir_size(
+    root_path: &Path,
+    dir_path: &Path,
+    walk_results: &mut DirWalker,
+    ignorer: &Ignorer,
+) {
     if let Ok(walker) = read_dir(dir_path) {
         for dir_ent in walker {
             if let Ok((path, Ok(meta))) = dir_ent.map(|e| (e.path(), e.metadata())) {
-                if path.is_dir() {
-                    recurse_total_dir_size(&path, walk_results);
+                let is_dir = path.is_dir();
+                if ignorer.is_ignored(root_path, &path, is_dir) {
+                    continue;
+                }
+                if is_dir {
+                    recurse_total_dir_size(root_path, &path, walk_results, ignorer);
                 }
 
                 if let Ok(file_disk_size) = path.size_on_disk_fast(&meta) {
@@ -63,9 +76,9 @@ fn recurse_total_dir_size(dir_path: &Path, walk_results: &mut DirWalker) {
 }
 
 /// only temporary layout for DirMeta struct.
-pub fn get_dir_meta(dir_path: &Path) -> Result<DirMeta> {
+pub fn get_dir_meta(dir_path: &Path, ignorer: &Ignorer) -> Result<DirMeta> {
     let mut dir_walker = DirWalker::default();
-    recurse_total_dir_size(dir_path, &mut dir_walker);
+    recurse_total_dir_size(dir_path, dir_path, &mut dir_walker, ignorer);
 
     if dir_walker.io_err_count > 0 {
         eprintln!("{} io errors encountered.", dir_walker.io_err_count);
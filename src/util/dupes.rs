@@ -0,0 +1,180 @@
+use crate::{prelude::*, util::dirs::human_readable_bytes};
+use filesize::PathExt;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{File, Metadata},
+    io::{copy, Read},
+    path::{Path, PathBuf},
+};
+
+/// Bytes hashed from the start of each candidate before hashing whole files
+const PARTIAL_HASH_BYTES: u64 = 16 * 1024;
+
+/// Files with identical contents, sorted by path. Removing all but the first reclaims space.
+#[derive(Debug, Serialize)]
+pub struct DupeGroup {
+    /// blake3 hash of the contents, in hex
+    pub hash: String,
+    pub size: u64,
+    /// space the first copy takes on disk
+    pub disk_size: u64,
+    pub reclaimable: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl fmt::Display for DupeGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} copies of {}, {} reclaimable",
+            self.paths.len(),
+            human_readable_bytes(self.size),
+            human_readable_bytes(self.reclaimable)
+        )?;
+        for path in &self.paths {
+            write!(f, "\n    {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Identity of the data behind a path, hard links to one file share it
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn hash_file(path: &Path, limit: Option<u64>) -> Result<blake3::Hash> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match limit {
+        Some(limit) => copy(&mut file.take(limit), &mut hasher)?,
+        None => copy(&mut &file, &mut hasher)?,
+    };
+    Ok(hasher.finalize())
+}
+
+/// Splits every group by a key computed per path, keeping only keys shared by several paths.
+/// Paths whose key can't be computed are reported and dropped.
+fn refine<K, F>(groups: Vec<Vec<PathBuf>>, key: F) -> Vec<(K, Vec<PathBuf>)>
+where
+    K: Eq + std::hash::Hash,
+    F: Fn(&Path) -> Result<K>,
+{
+    let mut refined = Vec::new();
+    for group in groups {
+        let mut by_key: HashMap<K, Vec<PathBuf>> = HashMap::new();
+        for path in group {
+            match key(&path) {
+                Ok(key) => by_key.entry(key).or_default().push(path),
+                Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        refined.extend(by_key.into_iter().filter(|(_, paths)| paths.len() > 1));
+    }
+    refined
+}
+
+/// Groups `files` with identical contents, narrowing candidates by size, then by a hash of their
+/// first bytes, and only then by a hash of the whole file. Hard links to one another count once,
+/// since removing them frees nothing. Groups come largest reclaimable space first.
+pub fn find_dupes(files: impl IntoIterator<Item = PathBuf>, min_size: u64) -> Vec<DupeGroup> {
+    let mut seen = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut sizes = HashMap::new();
+
+    for path in files {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+        if metadata.len() < min_size {
+            continue;
+        }
+        if let Some(id) = file_id(&metadata) {
+            if !seen.insert(id) {
+                continue;
+            }
+        }
+
+        let disk_size = path.size_on_disk_fast(&metadata).unwrap_or(metadata.len());
+        sizes.insert(path.clone(), (metadata.len(), disk_size));
+        by_size.entry(metadata.len()).or_default().push(path);
+    }
+
+    let candidates = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(_, paths)| paths)
+        .collect();
+    let (whole, partial): (Vec<_>, Vec<_>) =
+        refine(candidates, |path| hash_file(path, Some(PARTIAL_HASH_BYTES)))
+            .into_iter()
+            .partition(|(_, paths)| sizes[&paths[0]].0 <= PARTIAL_HASH_BYTES);
+    let full = refine(
+        partial.into_iter().map(|(_, paths)| paths).collect(),
+        |path| hash_file(path, None),
+    );
+
+    let mut groups: Vec<_> = whole
+        .into_iter()
+        .chain(full)
+        .map(|(hash, mut paths)| {
+            paths.sort_unstable();
+            let (size, disk_size) = sizes[&paths[0]];
+            let reclaimable = paths[1..].iter().map(|path| sizes[path].1).sum();
+
+            DupeGroup {
+                hash: hash.to_hex().to_string(),
+                size,
+                disk_size,
+                reclaimable,
+                paths,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.reclaimable
+            .cmp(&a.reclaimable)
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+#[test]
+fn finds_dupes() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+
+    // same size and same first bytes, only the tail tells them apart
+    let same = vec![7u8; PARTIAL_HASH_BYTES as usize + 10];
+    let mut other = same.clone();
+    *other.last_mut().unwrap() = 8;
+    for (name, contents) in &[("a", &same), ("b", &same), ("c", &other)] {
+        std::fs::write(dir.join(name), contents)?;
+    }
+    std::fs::write(dir.join("d"), "buo")?;
+    std::fs::write(dir.join("e"), "buo")?;
+    std::fs::hard_link(dir.join("a"), dir.join("a-link"))?;
+
+    let files: Vec<_> = ["a", "a-link", "b", "c", "d", "e"]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+    let groups = find_dupes(files.clone(), 0);
+    let large_only = find_dupes(files, 1024);
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].paths, vec![dir.join("a"), dir.join("b")]);
+    assert_eq!(groups[0].reclaimable, groups[0].disk_size);
+    assert_eq!(groups[1].paths, vec![dir.join("d"), dir.join("e")]);
+    assert_eq!(large_only.len(), 1);
+    Ok(())
+}
@@ -1,5 +1,15 @@
 use serde::Deserialize;
-use strum::{AsRefStr, EnumIter, IntoEnumIterator};
+use strum::{AsRefStr, EnumIter, EnumString, IntoEnumIterator};
+
+#[derive(AsRefStr, Copy, Clone, Debug, EnumIter, EnumString, Hash, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum FileCategory {
+    Audio,
+    Video,
+    Text,
+    Img,
+    Dev,
+}
 
 #[derive(AsRefStr, Copy, Clone, Debug, Deserialize, EnumIter, Hash, PartialEq, Eq)]
 #[strum(serialize_all = "lowercase")]
@@ -113,6 +123,22 @@ impl FileExt {
     pub fn is_matroska(&self) -> bool {
         matches!(self, Self::Mkv | Self::Webm)
     }
+
+    pub fn category(&self) -> Option<FileCategory> {
+        if self.is_audio() {
+            Some(FileCategory::Audio)
+        } else if self.is_video() {
+            Some(FileCategory::Video)
+        } else if self.is_text() {
+            Some(FileCategory::Text)
+        } else if self.is_img() {
+            Some(FileCategory::Img)
+        } else if self.is_dev() {
+            Some(FileCategory::Dev)
+        } else {
+            None
+        }
+    }
 }
 
 impl Default for FileExt {
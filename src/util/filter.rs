@@ -0,0 +1,64 @@
+use crate::{prelude::*, util::file_types::FileCategory};
+use std::path::Path;
+
+/// Include and exclude rules by file category and extension.
+/// Empty include lists let everything through, excludes always win.
+#[derive(Clone, Default)]
+pub struct FileFilter {
+    pub types: Vec<FileCategory>,
+    pub exts: Vec<String>,
+    pub exclude_types: Vec<FileCategory>,
+    pub exclude_exts: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+            && self.exts.is_empty()
+            && self.exclude_types.is_empty()
+            && self.exclude_exts.is_empty()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let ext = get_file_ext(path).map(|ext| ext.to_lowercase());
+        let category = ext.as_deref().and_then(|ext| FileExt::from(ext).category());
+
+        let has_ext = |exts: &[String]| matches!(&ext, Some(ext) if exts.iter().any(|e| e.eq_ignore_ascii_case(ext)));
+        let has_category = |categories: &[FileCategory]| matches!(category, Some(category) if categories.contains(&category));
+
+        if has_ext(&self.exclude_exts) || has_category(&self.exclude_types) {
+            return false;
+        }
+
+        // an extension or category listed in either include list is enough
+        let includes_all = self.types.is_empty() && self.exts.is_empty();
+        includes_all || has_ext(&self.exts) || has_category(&self.types)
+    }
+}
+
+#[test]
+fn filters_by_category_and_ext() {
+    let filter = FileFilter {
+        types: vec![FileCategory::Video],
+        exts: vec!["flac".into()],
+        exclude_exts: vec!["webm".into()],
+        ..Default::default()
+    };
+
+    assert!(filter.matches(Path::new("movie.mkv")));
+    assert!(filter.matches(Path::new("track.FLAC")));
+    assert!(!filter.matches(Path::new("track.mp3")));
+    assert!(!filter.matches(Path::new("clip.webm")));
+    assert!(!filter.matches(Path::new("no_extension")));
+
+    let exclude_only = FileFilter {
+        exclude_types: vec![FileCategory::Dev],
+        ..Default::default()
+    };
+    assert!(exclude_only.matches(Path::new("track.mp3")));
+    assert!(!exclude_only.matches(Path::new("main.rs")));
+}
@@ -0,0 +1,122 @@
+use crate::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Cheap identity of a file's contents, a change in any field means it must be re-analyzed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Fingerprint {
+    pub mtime_ns: i64,
+    pub size: u64,
+    pub inode: u64,
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> u64 {
+    0
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Result<Self> {
+        Ok(Self::from(&path.metadata()?))
+    }
+}
+
+impl From<&Metadata> for Fingerprint {
+    fn from(metadata: &Metadata) -> Self {
+        let mtime_ns = metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_nanos() as i64)
+            .unwrap_or_default();
+
+        Self {
+            mtime_ns,
+            size: metadata.len(),
+            inode: inode(metadata),
+        }
+    }
+}
+
+/// Files found by a walk, split by how they compare to the previously indexed fingerprints
+#[derive(Debug, Default)]
+pub struct FingerprintDiff {
+    pub added: Vec<(PathBuf, Fingerprint)>,
+    pub modified: Vec<(PathBuf, Fingerprint)>,
+    pub removed: Vec<PathBuf>,
+    pub unchanged: usize,
+}
+
+/// Compares walked paths against known fingerprints. Known paths missing from the walk only count
+/// as removed once they are gone from disk, since filters and limits also shorten a walk.
+pub fn diff_fingerprints(
+    mut known: HashMap<PathBuf, Option<Fingerprint>>,
+    walked: Vec<PathBuf>,
+) -> FingerprintDiff {
+    let mut diff = FingerprintDiff::default();
+
+    for path in walked {
+        let current = match Fingerprint::of(&path) {
+            Ok(fingerprint) => fingerprint,
+            // vanished between the walk and now, the next run will pick up the removal
+            Err(_) => continue,
+        };
+
+        match known.remove(&path) {
+            None => diff.added.push((path, current)),
+            Some(Some(previous)) if previous == current => diff.unchanged += 1,
+            Some(_) => diff.modified.push((path, current)),
+        }
+    }
+
+    diff.removed = known.into_keys().filter(|path| !path.is_file()).collect();
+    diff.removed.sort_unstable();
+    diff
+}
+
+#[test]
+fn diffs_fingerprints() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path();
+    let (same, changed, new, gone, filtered) = (
+        dir.join("same"),
+        dir.join("changed"),
+        dir.join("new"),
+        dir.join("gone"),
+        dir.join("filtered"),
+    );
+    for path in &[&same, &changed, &new, &filtered] {
+        std::fs::write(path, "buo")?;
+    }
+
+    let mut known = HashMap::new();
+    known.insert(same.clone(), Some(Fingerprint::of(&same)?));
+    known.insert(changed.clone(), Some(Fingerprint::of(&changed)?));
+    known.insert(gone.clone(), Some(Fingerprint::of(&same)?));
+    known.insert(filtered.clone(), Some(Fingerprint::of(&filtered)?));
+    std::fs::write(&changed, "buo buo")?;
+
+    let diff = diff_fingerprints(known, vec![same, changed.clone(), new.clone()]);
+
+    assert_eq!(diff.unchanged, 1);
+    assert_eq!(
+        diff.added.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        vec![&new]
+    );
+    assert_eq!(
+        diff.modified.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        vec![&changed]
+    );
+    assert_eq!(diff.removed, vec![gone]);
+    Ok(())
+}
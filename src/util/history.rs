@@ -0,0 +1,100 @@
+use crate::{
+    prelude::*,
+    util::{
+        config::HistoryConfig,
+        index::{MetaIndex, SearchHit},
+        ranker::text_match,
+    },
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Every visit counts as 1 when it happens and halves in weight every `half_life_days`,
+/// so files opened often and lately rank above ones opened often long ago
+pub fn frecency(visits: &[i64], now: i64, half_life_days: f64) -> f64 {
+    visits
+        .iter()
+        .map(|&visited_at| {
+            let age_days = (now - visited_at).max(0) as f64 / SECS_PER_DAY;
+            0.5_f64.powf(age_days / half_life_days)
+        })
+        .sum()
+}
+
+/// Frecency of every visited file in the index
+pub fn frecencies(index: &MetaIndex, config: &HistoryConfig) -> Result<HashMap<PathBuf, f64>> {
+    let now = unix_now();
+    Ok(index
+        .visits()?
+        .into_iter()
+        .map(|(path, visits)| {
+            let score = frecency(&visits, now, config.half_life_days);
+            (path, score)
+        })
+        .collect())
+}
+
+/// Adds weighted frecency to each hit's relevance relative to the best one, so the weight means
+/// the same whatever the query, and orders hits by the sum, best first
+pub fn rank_hits(
+    hits: &mut [SearchHit],
+    frecencies: &HashMap<PathBuf, f64>,
+    config: &HistoryConfig,
+) {
+    let best = hits
+        .iter()
+        .filter_map(|hit| hit.relevance)
+        .fold(0.0, f64::max);
+    for hit in hits.iter_mut() {
+        hit.frecency = frecencies
+            .get(&hit.meta.file_path)
+            .copied()
+            .unwrap_or_default();
+        hit.score = text_match(hit.relevance, best) + config.weight * hit.frecency;
+    }
+    hits.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+#[test]
+fn decays_visits() {
+    let day = SECS_PER_DAY as i64;
+    let now = 100 * day;
+
+    assert_eq!(frecency(&[now, now], now, 7.0), 2.0);
+    assert!((frecency(&[now - 7 * day], now, 7.0) - 0.5).abs() < 1e-9);
+    assert!(frecency(&[now - day; 3], now, 7.0) > frecency(&[now - 30 * day; 5], now, 7.0));
+    assert_eq!(frecency(&[], now, 7.0), 0.0);
+}
+
+#[test]
+fn weighs_frecency_against_relative_relevance() {
+    let hit = |path: &str, relevance| {
+        let meta = MediaMeta {
+            file_path: PathBuf::from(path),
+            ..MediaMeta::default()
+        };
+        SearchHit::new(meta, Some(relevance))
+    };
+    let mut hits = vec![hit("a", 20.0), hit("b", 16.0)];
+    let frecencies = vec![(PathBuf::from("b"), 0.5)].into_iter().collect();
+
+    rank_hits(&mut hits, &frecencies, &HistoryConfig::default());
+    assert_eq!(hits[0].meta.file_path, PathBuf::from("b"));
+    assert!((hits[0].score - 1.3).abs() < 1e-9);
+}
@@ -0,0 +1,666 @@
+use crate::{
+    prelude::*,
+    util::{dev::LangStats, fingerprint::Fingerprint, history::unix_now},
+};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const INDEX_DIR: &str = "buo";
+const INDEX_FILE: &str = "index.sqlite";
+
+/// Each entry upgrades the schema by one `user_version`, never edit a released one
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE files (
+        id          INTEGER PRIMARY KEY,
+        path        TEXT NOT NULL UNIQUE,
+        file_name   TEXT NOT NULL,
+        indexed_at  INTEGER NOT NULL
+    );
+    CREATE TABLE metadata (
+        file_id       INTEGER PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+        title         TEXT,
+        author        TEXT,
+        duration_ms   INTEGER,
+        date          TEXT,
+        has_stats     INTEGER NOT NULL DEFAULT 0,
+        display_extra INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE lang_stats (
+        file_id   INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+        name      TEXT NOT NULL,
+        loc       INTEGER,
+        comments  INTEGER,
+        PRIMARY KEY (file_id, name)
+    );
+    CREATE TABLE extras (
+        file_id   INTEGER PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+        content   TEXT NOT NULL
+    );
+",
+    "
+    ALTER TABLE files ADD COLUMN mtime_ns INTEGER;
+    ALTER TABLE files ADD COLUMN size INTEGER;
+    ALTER TABLE files ADD COLUMN inode INTEGER;
+",
+    "
+    CREATE VIRTUAL TABLE search USING fts5(file_name, title, author, extra, prefix = '2 3');
+    INSERT INTO search (rowid, file_name, title, author, extra)
+        SELECT f.id, f.file_name, m.title, m.author, e.content
+        FROM files f
+        LEFT JOIN metadata m ON m.file_id = f.id
+        LEFT JOIN extras e ON e.file_id = f.id;
+    CREATE TRIGGER files_search_delete AFTER DELETE ON files BEGIN
+        DELETE FROM search WHERE rowid = old.id;
+    END;
+",
+    "
+    CREATE TABLE visits (
+        file_id     INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+        visited_at  INTEGER NOT NULL
+    );
+    CREATE INDEX visits_by_file ON visits (file_id, visited_at);
+",
+    "
+    CREATE TABLE no_metadata (
+        path      TEXT PRIMARY KEY,
+        mtime_ns  INTEGER NOT NULL,
+        size      INTEGER NOT NULL,
+        inode     INTEGER NOT NULL
+    );
+",
+];
+
+/// Columns read by `read_meta_row`, in order
+const META_COLUMNS: &str = "f.id, f.path, f.file_name, m.title, m.author, m.duration_ms, m.date,
+    m.has_stats, m.display_extra, e.content";
+
+const META_JOINS: &str = "LEFT JOIN metadata m ON m.file_id = f.id
+    LEFT JOIN extras e ON e.file_id = f.id";
+
+/// Markers wrapped around matched terms in search snippets
+const HIGHLIGHT_START: &str = "[";
+const HIGHLIGHT_END: &str = "]";
+const SNIPPET_TOKENS: usize = 12;
+
+/// Older visits barely move frecency, so only the most recent ones are kept per file
+const MAX_VISITS_PER_FILE: usize = 32;
+
+/// Location of the metadata index, creating its parent directory if needed
+pub fn default_index_path() -> Result<PathBuf> {
+    let mut index_path = dirs::data_dir().ok_or_else(|| anyhow!("Unable to locate data dir"))?;
+    index_path.push(INDEX_DIR);
+    std::fs::create_dir_all(&index_path)?;
+
+    index_path.push(INDEX_FILE);
+    Ok(index_path)
+}
+
+/// SQLite backed store of analyzed files, keyed by path
+pub struct MetaIndex {
+    conn: Connection,
+}
+
+impl MetaIndex {
+    /// Opens or creates the index at `path` and brings its schema up to date
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+            .map_err(|e| e.context(format!("Unable to open index at {}", path.display())))
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    pub fn schema_version(&self) -> Result<usize> {
+        schema_version(&self.conn)
+    }
+
+    /// Inserts or replaces the entry for `meta.file_path`. Entries without a fingerprint are
+    /// always treated as modified by incremental indexing.
+    pub fn upsert(&mut self, meta: &MediaMeta, fingerprint: Option<Fingerprint>) -> Result<()> {
+        self.upsert_all(std::iter::once((meta, fingerprint)))
+            .map(|_| ())
+    }
+
+    /// Upserts every entry inside a single transaction, returning how many were written
+    pub fn upsert_all<'m>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'m MediaMeta, Option<Fingerprint>)>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut written = 0;
+        for (meta, fingerprint) in entries {
+            upsert_in(&tx, meta, fingerprint)?;
+            written += 1;
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    /// Remembers files that yielded no metadata along with their fingerprint, dropping any
+    /// outdated entry for them, so that they're only analyzed again once they change
+    pub fn mark_no_metadata<'f>(
+        &mut self,
+        files: impl IntoIterator<Item = &'f (PathBuf, Fingerprint)>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut marked = 0;
+        for (path, fingerprint) in files {
+            tx.execute("DELETE FROM files WHERE path = ?1", params![path_key(path)])?;
+            tx.execute(
+                "INSERT OR REPLACE INTO no_metadata (path, mtime_ns, size, inode)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    path_key(path),
+                    fingerprint.mtime_ns,
+                    fingerprint.size as i64,
+                    fingerprint.inode as i64
+                ],
+            )?;
+            marked += 1;
+        }
+        tx.commit()?;
+        Ok(marked)
+    }
+
+    /// Fingerprints recorded for every path inside `root`, indexed or without metadata
+    pub fn fingerprints_under(&self, root: &Path) -> Result<HashMap<PathBuf, Option<Fingerprint>>> {
+        // a range rather than LIKE, so lookups use the path indexes; '0' sorts right after '/'
+        let mut stmt = self.conn.prepare(
+            "SELECT path, mtime_ns, size, inode FROM files
+             WHERE path = ?1 OR (path >= ?1 || '/' AND path < ?1 || '0')
+             UNION ALL SELECT path, mtime_ns, size, inode FROM no_metadata
+             WHERE path = ?1 OR (path >= ?1 || '/' AND path < ?1 || '0')",
+        )?;
+        let root = path_key(root);
+        let rows = stmt.query_map(params![root.trim_end_matches('/')], |row| {
+            let path = PathBuf::from(row.get::<_, String>(0)?);
+            let fingerprint = match (row.get(1)?, row.get(2)?, row.get(3)?) {
+                (Some(mtime_ns), Some::<i64>(size), Some::<i64>(inode)) => Some(Fingerprint {
+                    mtime_ns,
+                    size: size as u64,
+                    inode: inode as u64,
+                }),
+                _ => None,
+            };
+            Ok((path, fingerprint))
+        })?;
+
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get(&self, path: &Path) -> Result<Option<MediaMeta>> {
+        let sql = format!(
+            "SELECT {} FROM files f {} WHERE f.path = ?1",
+            META_COLUMNS, META_JOINS
+        );
+        let row = self
+            .conn
+            .query_row(&sql, params![path_key(path)], read_meta_row)
+            .optional()?;
+
+        row.map(|(id, meta)| self.with_stats(id, meta)).transpose()
+    }
+
+    /// Full text search over file names, titles, authors and extras, best matches first.
+    /// See `fts_query` for the accepted term syntax.
+    pub fn search(&self, terms: &[String]) -> Result<Vec<SearchHit>> {
+        let sql = format!(
+            "SELECT {}, snippet(search, -1, ?2, ?3, '...', ?4), -s.rank
+             FROM search s JOIN files f ON f.id = s.rowid {}
+             WHERE search MATCH ?1 ORDER BY s.rank",
+            META_COLUMNS, META_JOINS
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(
+                params![
+                    fts_query(terms)?,
+                    HIGHLIGHT_START,
+                    HIGHLIGHT_END,
+                    SNIPPET_TOKENS as i64
+                ],
+                |row| {
+                    Ok((
+                        read_meta_row(row)?,
+                        row.get::<_, Option<String>>(10)?,
+                        row.get::<_, f64>(11)?,
+                    ))
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|((id, meta), snippet, relevance)| {
+                let mut hit = SearchHit::new(self.with_stats(id, meta)?, Some(relevance));
+                hit.snippet = snippet.filter(|snippet| !snippet.is_empty());
+                Ok(hit)
+            })
+            .collect()
+    }
+
+    /// Every entry, ordered by path
+    pub fn entries(&self) -> Result<Vec<MediaMeta>> {
+        let sql = format!(
+            "SELECT {} FROM files f {} ORDER BY f.path",
+            META_COLUMNS, META_JOINS
+        );
+        self.collect_rows(&sql, [])
+    }
+
+    /// Removes the entry for `path`, returning whether one existed
+    pub fn remove(&mut self, path: &Path) -> Result<bool> {
+        self.conn.execute(
+            "DELETE FROM no_metadata WHERE path = ?1",
+            params![path_key(path)],
+        )?;
+        let removed = self
+            .conn
+            .execute("DELETE FROM files WHERE path = ?1", params![path_key(path)])?;
+        Ok(removed > 0)
+    }
+
+    /// Records a visit to an indexed file, returning `false` if `path` isn't indexed
+    pub fn record_visit(&mut self, path: &Path, visited_at: i64) -> Result<bool> {
+        let tx = self.conn.transaction()?;
+        let file_id: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM files WHERE path = ?1",
+                params![path_key(path)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let file_id = match file_id {
+            Some(file_id) => file_id,
+            None => return Ok(false),
+        };
+
+        tx.execute(
+            "INSERT INTO visits (file_id, visited_at) VALUES (?1, ?2)",
+            params![file_id, visited_at],
+        )?;
+        tx.execute(
+            "DELETE FROM visits WHERE file_id = ?1 AND rowid NOT IN (
+                SELECT rowid FROM visits WHERE file_id = ?1 ORDER BY visited_at DESC LIMIT ?2
+            )",
+            params![file_id, MAX_VISITS_PER_FILE as i64],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Visit timestamps of every visited file
+    pub fn visits(&self) -> Result<HashMap<PathBuf, Vec<i64>>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.path, v.visited_at FROM visits v JOIN files f ON f.id = v.file_id",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?;
+
+        let mut visits: HashMap<PathBuf, Vec<i64>> = HashMap::new();
+        for row in rows {
+            let (path, visited_at) = row?;
+            visits
+                .entry(PathBuf::from(path))
+                .or_default()
+                .push(visited_at);
+        }
+        Ok(visits)
+    }
+
+    /// Removes every entry in `paths` inside a single transaction, returning how many existed.
+    /// Files remembered as having no metadata are forgotten and counted too.
+    pub fn remove_all<'p>(
+        &mut self,
+        paths: impl IntoIterator<Item = &'p PathBuf>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut removed = 0;
+        for path in paths {
+            removed += tx.execute("DELETE FROM files WHERE path = ?1", params![path_key(path)])?;
+            removed += tx.execute(
+                "DELETE FROM no_metadata WHERE path = ?1",
+                params![path_key(path)],
+            )?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        let len: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM files", [], |row| row.get(0))?;
+        Ok(len as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    fn collect_rows(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<MediaMeta>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params, read_meta_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(id, meta)| self.with_stats(id, meta))
+            .collect()
+    }
+
+    fn with_stats(&self, file_id: i64, mut meta: MediaMeta) -> Result<MediaMeta> {
+        if meta.stats.is_none() {
+            return Ok(meta);
+        }
+
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, loc, comments FROM lang_stats WHERE file_id = ?1 ORDER BY name",
+        )?;
+        let stats = stmt
+            .query_map(params![file_id], |row| {
+                Ok(LangStats {
+                    name: row.get(0)?,
+                    loc: row.get::<_, Option<i64>>(1)?.and_then(non_zero),
+                    comments: row.get::<_, Option<i64>>(2)?.and_then(non_zero),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        meta.stats = Some(stats);
+        Ok(meta)
+    }
+}
+
+/// Quotes every term so user input can't inject fts5 syntax. Terms ending in `*` match
+/// prefixes, and terms holding several words (quoted on the command line) match as phrases.
+fn fts_query(terms: &[String]) -> Result<String> {
+    let mut query = Vec::with_capacity(terms.len());
+    for term in terms {
+        let term = term.trim();
+        let (term, prefix) = match term.strip_suffix('*') {
+            Some(term) => (term, "*"),
+            None => (term, ""),
+        };
+        let term = term.trim_matches('"').trim();
+        if !term.is_empty() {
+            query.push(format!("\"{}\"{}", term.replace('"', "\"\""), prefix));
+        }
+    }
+
+    if query.is_empty() {
+        bail!("No search terms provided!");
+    }
+    Ok(query.join(" "))
+}
+
+fn schema_version(conn: &Connection) -> Result<usize> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version = schema_version(conn)?;
+    if version > MIGRATIONS.len() {
+        bail!(
+            "Index schema version {} is newer than the latest supported version {}",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+
+    for (n, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", &((n + 1) as i64))?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn non_zero(n: i64) -> Option<NonZeroUsize> {
+    NonZeroUsize::new(n as usize)
+}
+
+fn date_to_text(date: &DateKind) -> String {
+    match date {
+        DateKind::Chrono(date) => date.to_rfc3339(),
+        DateKind::Sym(date) => date.clone(),
+    }
+}
+
+fn date_from_text(date: String) -> DateKind {
+    match DateTime::parse_from_rfc3339(&date) {
+        Ok(parsed) => DateKind::Chrono(parsed.with_timezone(&Utc)),
+        Err(_) => DateKind::Sym(date),
+    }
+}
+
+fn read_meta_row(row: &Row) -> rusqlite::Result<(i64, MediaMeta)> {
+    let has_stats: Option<bool> = row.get(7)?;
+    let meta = MediaMeta {
+        file_path: PathBuf::from(row.get::<_, String>(1)?),
+        file_name: row.get(2)?,
+        title: row.get(3)?,
+        author: row.get(4)?,
+        duration: row
+            .get::<_, Option<i64>>(5)?
+            .map(|ms| Duration::from_millis(ms as u64)),
+        date: row.get::<_, Option<String>>(6)?.map(date_from_text),
+        // filled in by `with_stats`, which only queries when this is `Some`
+        stats: has_stats.unwrap_or(false).then(Vec::new),
+        display_extra: row.get::<_, Option<bool>>(8)?.unwrap_or(false),
+        extra: row.get(9)?,
+    };
+    Ok((row.get(0)?, meta))
+}
+
+fn upsert_in(tx: &Transaction, meta: &MediaMeta, fingerprint: Option<Fingerprint>) -> Result<()> {
+    let indexed_at = unix_now();
+
+    tx.execute(
+        "DELETE FROM no_metadata WHERE path = ?1",
+        params![path_key(&meta.file_path)],
+    )?;
+    tx.execute(
+        "INSERT INTO files (path, file_name, indexed_at, mtime_ns, size, inode)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(path) DO UPDATE SET file_name = excluded.file_name,
+            indexed_at = excluded.indexed_at, mtime_ns = excluded.mtime_ns,
+            size = excluded.size, inode = excluded.inode",
+        params![
+            path_key(&meta.file_path),
+            meta.file_name,
+            indexed_at,
+            fingerprint.map(|f| f.mtime_ns),
+            fingerprint.map(|f| f.size as i64),
+            fingerprint.map(|f| f.inode as i64),
+        ],
+    )?;
+    let file_id: i64 = tx.query_row(
+        "SELECT id FROM files WHERE path = ?1",
+        params![path_key(&meta.file_path)],
+        |row| row.get(0),
+    )?;
+
+    tx.execute(
+        "INSERT OR REPLACE INTO metadata
+         (file_id, title, author, duration_ms, date, has_stats, display_extra)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            file_id,
+            meta.title,
+            meta.author,
+            meta.duration.map(|d| d.as_millis() as i64),
+            meta.date.as_ref().map(date_to_text),
+            meta.stats.is_some(),
+            meta.display_extra,
+        ],
+    )?;
+
+    tx.execute(
+        "DELETE FROM lang_stats WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    for stats in meta.stats.iter().flatten() {
+        tx.execute(
+            "INSERT OR REPLACE INTO lang_stats (file_id, name, loc, comments) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                file_id,
+                stats.name,
+                stats.loc.map(|n| n.get() as i64),
+                stats.comments.map(|n| n.get() as i64),
+            ],
+        )?;
+    }
+
+    match meta.extra {
+        Some(ref extra) => tx.execute(
+            "INSERT OR REPLACE INTO extras (file_id, content) VALUES (?1, ?2)",
+            params![file_id, extra],
+        )?,
+        None => tx.execute("DELETE FROM extras WHERE file_id = ?1", params![file_id])?,
+    };
+
+    tx.execute("DELETE FROM search WHERE rowid = ?1", params![file_id])?;
+    tx.execute(
+        "INSERT INTO search (rowid, file_name, title, author, extra) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![file_id, meta.file_name, meta.title, meta.author, meta.extra],
+    )?;
+    Ok(())
+}
+
+/// An indexed entry matched by `MetaIndex::search`
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub meta: MediaMeta,
+    /// matched text with terms wrapped in `HIGHLIGHT_START` and `HIGHLIGHT_END`
+    pub snippet: Option<String>,
+    /// full text relevance, higher is better, absent when no words were searched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevance: Option<f64>,
+    /// decayed visit count, see `history::frecency`
+    pub frecency: f64,
+    /// what results are ordered by: relevance and weighted frecency combined, or the
+    /// probability of being picked once the ranker is trained
+    pub score: f64,
+}
+
+impl SearchHit {
+    pub fn new(meta: MediaMeta, relevance: Option<f64>) -> Self {
+        Self {
+            meta,
+            snippet: None,
+            relevance,
+            frecency: 0.0,
+            score: relevance.unwrap_or_default(),
+        }
+    }
+}
+
+use std::fmt;
+impl fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.meta.to_string().trim_end())?;
+        if let Some(ref snippet) = self.snippet {
+            write!(f, "\nmatch: {}", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn index_roundtrip() -> Result<()> {
+    let mut index = MetaIndex::open_in_memory()?;
+    assert_eq!(index.schema_version()?, MIGRATIONS.len());
+
+    let song = MediaMeta {
+        file_path: PathBuf::from("/music/song.flac"),
+        file_name: "song.flac".to_owned(),
+        title: Some("Blue Monday".to_owned()),
+        author: Some("New Order".to_owned()),
+        duration: Some(Duration::from_secs(448)),
+        date: Some(DateKind::Sym("1983".to_owned())),
+        ..Default::default()
+    };
+    let code = MediaMeta {
+        file_path: PathBuf::from("/src/main.rs"),
+        file_name: "main.rs".to_owned(),
+        stats: Some(vec![LangStats {
+            name: "Rust".to_owned(),
+            loc: NonZeroUsize::new(12),
+            comments: None,
+        }]),
+        extra: Some("readme".to_owned()),
+        ..Default::default()
+    };
+    index.upsert_all(vec![(&song, None), (&code, None)])?;
+
+    let found = index.get(&song.file_path)?.expect("song was indexed");
+    assert_eq!(found.title.as_deref(), Some("Blue Monday"));
+    assert_eq!(found.duration, song.duration);
+    assert!(found.stats.is_none());
+
+    let found = index.search(&["NEW".to_owned(), "ord*".to_owned()])?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].meta.file_path, song.file_path);
+    assert_eq!(found[0].snippet.as_deref(), Some("[New] [Order]"));
+    assert!(index.search(&["order new".to_owned()])?.is_empty());
+    assert_eq!(index.search(&["\"blue monday\"".to_owned()])?.len(), 1);
+
+    let mut retitled = song.clone();
+    retitled.title = None;
+    index.upsert(&retitled, None)?;
+    assert_eq!(index.len()?, 2);
+    assert!(index.search(&["monday".to_owned()])?.is_empty());
+
+    let stats = index.get(&code.file_path)?.and_then(|meta| meta.stats);
+    assert_eq!(stats.map(|stats| stats[0].loc), Some(NonZeroUsize::new(12)));
+
+    assert!(index.remove(&code.file_path)?);
+    assert!(!index.remove(&code.file_path)?);
+    assert!(index.record_visit(&song.file_path, 10)?);
+    assert!(!index.record_visit(&code.file_path, 10)?);
+    assert_eq!(index.visits()?.get(&song.file_path), Some(&vec![10]));
+    assert_eq!(index.len()?, 1);
+
+    // a file that lost its metadata keeps only its fingerprint, and gets it back on upsert
+    let fingerprint = Fingerprint {
+        mtime_ns: 1,
+        size: 2,
+        inode: 3,
+    };
+    index.mark_no_metadata(&[(song.file_path.clone(), fingerprint)])?;
+    assert!(index.get(&song.file_path)?.is_none());
+    let fingerprints = index.fingerprints_under(Path::new("/music"))?;
+    assert_eq!(fingerprints.get(&song.file_path), Some(&Some(fingerprint)));
+    index.upsert(&song, None)?;
+    assert_eq!(index.fingerprints_under(Path::new("/music"))?.len(), 1);
+
+    // siblings sharing the root as a prefix are not inside it
+    let sibling = PathBuf::from("/musical/b.mp3");
+    index.mark_no_metadata(&[(sibling.clone(), fingerprint)])?;
+    assert_eq!(index.fingerprints_under(Path::new("/music/"))?.len(), 1);
+    assert_eq!(index.fingerprints_under(&song.file_path)?.len(), 1);
+    assert_eq!(index.fingerprints_under(Path::new("/"))?.len(), 2);
+    assert_eq!(index.remove_all(&[song.file_path.clone(), sibling])?, 2);
+    Ok(())
+}
@@ -1,13 +1,60 @@
 use crate::prelude::*;
 use chrono::{DateTime, Utc};
-use std::fmt;
+use std::{fmt, path::PathBuf};
 
 #[derive(Serialize, strum::Display)]
 pub enum ExportKind {
+    Bookmark,
     Dir,
+    Duplicates,
     File,
 }
 
+#[derive(Clone, Copy, PartialEq, Serialize, strum::AsRefStr)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum ExportStatus {
+    Ok,
+    /// excluded by the type or extension filters
+    Skipped,
+    Unsupported,
+    NoMetadata,
+    IoError,
+    ParseError,
+}
+
+/// Parsers wrap io errors in their own types without exposing them as a source
+fn is_io_error(cause: &(dyn std::error::Error + 'static)) -> bool {
+    use matroska::MatroskaError;
+    use symphonia::core::errors::Error as SymphoniaError;
+
+    cause.is::<std::io::Error>()
+        || matches!(
+            cause.downcast_ref::<SymphoniaError>(),
+            Some(SymphoniaError::IoError(_))
+        )
+        || matches!(
+            cause.downcast_ref::<MatroskaError>(),
+            Some(MatroskaError::Io(_))
+        )
+}
+
+impl ExportStatus {
+    /// Io errors anywhere in the chain take precedence, anything else
+    /// is reported as a failure to parse the target
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if err.chain().any(is_io_error) {
+            Self::IoError
+        } else {
+            Self::ParseError
+        }
+    }
+
+    pub fn is_error(self) -> bool {
+        matches!(self, Self::IoError | Self::ParseError)
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExportedJson<T>
@@ -15,6 +62,7 @@ where
     T: Serialize + fmt::Display,
 {
     file_type: ExportKind,
+    status: ExportStatus,
     date: DateTime<Utc>,
     #[serde(flatten)]
     inner: T,
@@ -25,6 +73,7 @@ impl From<MediaMeta> for ExportedJson<MediaMeta> {
     fn from(media_meta: MediaMeta) -> Self {
         ExportedJson {
             file_type: ExportKind::File,
+            status: ExportStatus::Ok,
             date: Utc::now(),
             inner: media_meta,
         }
@@ -36,16 +85,127 @@ impl From<DirMeta> for ExportedJson<DirMeta> {
     fn from(dir_meta: DirMeta) -> Self {
         ExportedJson {
             file_type: ExportKind::Dir,
+            status: ExportStatus::Ok,
             date: Utc::now(),
             inner: dir_meta,
         }
     }
 }
 
+use super::web::WebBookmark;
+impl From<WebBookmark> for ExportedJson<WebBookmark> {
+    fn from(bookmark: WebBookmark) -> Self {
+        ExportedJson {
+            file_type: ExportKind::Bookmark,
+            status: ExportStatus::Ok,
+            date: Utc::now(),
+            inner: bookmark,
+        }
+    }
+}
+
+use super::index::SearchHit;
+impl From<SearchHit> for ExportedJson<SearchHit> {
+    fn from(hit: SearchHit) -> Self {
+        ExportedJson {
+            file_type: ExportKind::File,
+            status: ExportStatus::Ok,
+            date: Utc::now(),
+            inner: hit,
+        }
+    }
+}
+
+use super::dupes::DupeGroup;
+impl From<DupeGroup> for ExportedJson<DupeGroup> {
+    fn from(group: DupeGroup) -> Self {
+        ExportedJson {
+            file_type: ExportKind::Duplicates,
+            status: ExportStatus::Ok,
+            date: Utc::now(),
+            inner: group,
+        }
+    }
+}
+
+/// Record for a target that yielded no metadata, so that every target
+/// still produces a well-formed entry in machine readable output
+#[derive(Serialize)]
+pub struct TargetIssue {
+    pub file_path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for TargetIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ExportedJson<TargetIssue> {
+    pub fn unsupported(path: PathBuf, file_type: &str) -> Self {
+        ExportedJson {
+            file_type: ExportKind::File,
+            status: ExportStatus::Unsupported,
+            date: Utc::now(),
+            inner: TargetIssue {
+                file_path: path,
+                message: format!("Filetype not supported: {}", file_type),
+            },
+        }
+    }
+
+    pub fn skipped(path: PathBuf) -> Self {
+        ExportedJson {
+            file_type: ExportKind::File,
+            status: ExportStatus::Skipped,
+            date: Utc::now(),
+            inner: TargetIssue {
+                message: format!("Skipped {}: excluded by filters", path.display()),
+                file_path: path,
+            },
+        }
+    }
+
+    pub fn no_metadata(path: PathBuf) -> Self {
+        ExportedJson {
+            file_type: ExportKind::File,
+            status: ExportStatus::NoMetadata,
+            date: Utc::now(),
+            inner: TargetIssue {
+                message: format!("No metadata for {}", path.display()),
+                file_path: path,
+            },
+        }
+    }
+
+    pub fn failed(path: PathBuf, err: &anyhow::Error) -> Self {
+        let file_type = if path.is_dir() {
+            ExportKind::Dir
+        } else {
+            ExportKind::File
+        };
+
+        ExportedJson {
+            file_type,
+            status: ExportStatus::from_error(err),
+            date: Utc::now(),
+            inner: TargetIssue {
+                message: format!("{}: {:#}", path.display(), err),
+                file_path: path,
+            },
+        }
+    }
+}
+
 impl<T> ExportedJson<T>
 where
     T: Serialize + fmt::Display,
 {
+    pub fn status(&self) -> ExportStatus {
+        self.status
+    }
+
     pub fn as_pretty_json(&self) -> Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
@@ -55,11 +215,53 @@ where
     }
 }
 
+use super::delimited::DelimitedRecord;
+impl<T> ExportedJson<T>
+where
+    T: Serialize + fmt::Display + DelimitedRecord,
+{
+    pub fn as_delimited(&self, delimiter: char) -> String {
+        self.inner
+            .delimited_row()
+            .to_line(&self.file_type, self.status, delimiter)
+    }
+}
+
+use super::template::{Template, TemplateRecord, TemplateValue};
+impl<T> ExportedJson<T>
+where
+    T: Serialize + fmt::Display + TemplateRecord,
+{
+    pub fn render(&self, template: &Template) -> String {
+        template.render(&|field| match field {
+            "type" => Some(TemplateValue::Text(self.file_type.to_string())),
+            "status" => Some(TemplateValue::Text(self.status.as_ref().to_owned())),
+            field => self.inner.template_value(field),
+        })
+    }
+}
+
 impl<T> fmt::Display for ExportedJson<T>
 where
     T: Serialize + fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "type: {}\n{}", self.file_type, self.inner)
+        match self.status {
+            ExportStatus::Ok => write!(f, "type: {}\n{}", self.file_type, self.inner),
+            _ => write!(f, "{}", self.inner),
+        }
     }
 }
+
+#[test]
+fn classifies_wrapped_io_errors() {
+    use std::io::{Error, ErrorKind};
+
+    let io = || Error::new(ErrorKind::UnexpectedEof, "truncated");
+    let wrapped = anyhow::Error::new(symphonia::core::errors::Error::IoError(io()));
+    assert!(ExportStatus::from_error(&wrapped) == ExportStatus::IoError);
+    let wrapped = anyhow::Error::new(matroska::MatroskaError::Io(io()));
+    assert!(ExportStatus::from_error(&wrapped) == ExportStatus::IoError);
+    let parse = anyhow::Error::new(matroska::MatroskaError::InvalidID);
+    assert!(ExportStatus::from_error(&parse) == ExportStatus::ParseError);
+}
@@ -37,13 +37,65 @@ impl PartialEq<MediaMeta> for &Path {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+/// Serialized as the bare date or year in human readable formats, and tagged with its variant
+/// in binary ones such as the cache, which can't tell the two apart otherwise
+#[derive(Debug, Clone)]
 pub enum DateKind {
     Chrono(DateTime<Utc>),
     Sym(String),
 }
 
+#[derive(Serialize)]
+enum TaggedDateRef<'a> {
+    Chrono(&'a DateTime<Utc>),
+    Sym(&'a str),
+}
+
+#[derive(Deserialize)]
+enum TaggedDate {
+    Chrono(DateTime<Utc>),
+    Sym(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UntaggedDate {
+    Chrono(DateTime<Utc>),
+    Sym(String),
+}
+
+impl Serialize for DateKind {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Chrono(date) if serializer.is_human_readable() => date.serialize(serializer),
+            Self::Sym(year) if serializer.is_human_readable() => year.serialize(serializer),
+            Self::Chrono(date) => TaggedDateRef::Chrono(date).serialize(serializer),
+            Self::Sym(year) => TaggedDateRef::Sym(year).serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DateKind {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(match UntaggedDate::deserialize(deserializer)? {
+                UntaggedDate::Chrono(date) => Self::Chrono(date),
+                UntaggedDate::Sym(year) => Self::Sym(year),
+            })
+        } else {
+            Ok(match TaggedDate::deserialize(deserializer)? {
+                TaggedDate::Chrono(date) => Self::Chrono(date),
+                TaggedDate::Sym(year) => Self::Sym(year),
+            })
+        }
+    }
+}
+
 use std::fmt;
 impl fmt::Display for DateKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
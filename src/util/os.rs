@@ -3,3 +3,17 @@ pub enum Os {
     Mac,
     Windows,
 }
+
+impl Os {
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "linux") {
+            Some(Self::Linux)
+        } else if cfg!(target_os = "macos") {
+            Some(Self::Mac)
+        } else if cfg!(target_os = "windows") {
+            Some(Self::Windows)
+        } else {
+            None
+        }
+    }
+}
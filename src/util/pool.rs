@@ -0,0 +1,139 @@
+use crate::prelude::*;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, sync_channel, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// Text of a panic payload, which is usually a `&str` or a `String`
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Runs `job` over every input on `jobs` worker threads and hands the results
+/// to `sink` in input order, as soon as each one and all of its predecessors are done.
+/// Inputs are pulled lazily, so long or streaming iterators are never buffered whole.
+/// A panicking job fails the whole map once the results before it are sunk.
+pub fn ordered_par_map<T, R>(
+    inputs: impl Iterator<Item = T> + Send + 'static,
+    jobs: usize,
+    job: fn(T) -> R,
+    mut sink: impl FnMut(R) -> Result<()>,
+) -> Result<()>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+{
+    if jobs <= 1 {
+        for input in inputs {
+            sink(job(input))?;
+        }
+        return Ok(());
+    }
+
+    // bounded, so the producer never runs too far ahead of the workers
+    let (input_tx, input_rx) = sync_channel::<(usize, T)>(jobs * 2);
+    let (result_tx, result_rx) = channel::<(usize, thread::Result<R>)>();
+
+    thread::spawn(move || {
+        for indexed_input in inputs.enumerate() {
+            if input_tx.send(indexed_input).is_err() {
+                // every worker is gone, nothing left to feed
+                break;
+            }
+        }
+    });
+
+    let input_rx: Arc<Mutex<Receiver<(usize, T)>>> = Arc::new(Mutex::new(input_rx));
+    for _ in 0..jobs {
+        let input_rx = Arc::clone(&input_rx);
+        let result_tx = result_tx.clone();
+
+        thread::spawn(move || loop {
+            let next_input = match input_rx.lock() {
+                Ok(input_rx) => input_rx.recv(),
+                Err(_) => break,
+            };
+
+            match next_input {
+                Ok((index, input)) => {
+                    // a panic still sends a result, or every later one would wait on it forever
+                    let result = catch_unwind(AssertUnwindSafe(|| job(input)));
+                    if result_tx.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        });
+    }
+    // only the workers may keep the result channel open
+    drop(result_tx);
+
+    let mut pending = BTreeMap::new();
+    let mut next_index = 0;
+    for (index, result) in result_rx {
+        pending.insert(index, result);
+        while let Some(result) = pending.remove(&next_index) {
+            match result {
+                Ok(result) => sink(result)?,
+                Err(panic) => bail!(
+                    "A worker panicked on input #{}: {}",
+                    next_index,
+                    panic_message(&*panic)
+                ),
+            }
+            next_index += 1;
+        }
+    }
+
+    if !pending.is_empty() {
+        bail!("A worker thread exited before finishing its job");
+    }
+    Ok(())
+}
+
+#[test]
+fn preserves_input_order() -> Result<()> {
+    fn slow_square(n: u64) -> u64 {
+        thread::sleep(std::time::Duration::from_millis((10 - n % 10) * 2));
+        n * n
+    }
+
+    let mut results = vec![];
+    ordered_par_map(0..50u64, 4, slow_square, |r| {
+        results.push(r);
+        Ok(())
+    })?;
+
+    let expected: Vec<u64> = (0..50).map(|n| n * n).collect();
+    assert_eq!(results, expected);
+    Ok(())
+}
+
+#[test]
+fn reports_panicking_jobs() {
+    fn fragile(n: u64) -> u64 {
+        if n == 3 {
+            panic!("malformed input");
+        }
+        n
+    }
+
+    let mut results = vec![];
+    let outcome = ordered_par_map(0..100u64, 4, fragile, |r| {
+        results.push(r);
+        Ok(())
+    });
+    assert!(outcome.is_err());
+    assert_eq!(results, vec![0, 1, 2]);
+}
@@ -0,0 +1,498 @@
+use crate::{
+    prelude::*,
+    util::{
+        file_types::FileCategory,
+        template::{TemplateRecord, TemplateValue},
+    },
+};
+use chrono::{Datelike, NaiveDate};
+use std::{cmp::Ordering, fmt, path::Path, str::FromStr, time::Duration};
+
+const FIELDS: &[&str] = &[
+    "type", "name", "title", "author", "path", "ext", "extra", "lang", "duration", "date", "size",
+    "loc",
+];
+
+/// A parse failure pointing at the offending byte of the query
+#[derive(Debug)]
+pub struct QueryError {
+    query: String,
+    position: usize,
+    message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.query[..self.position].chars().count();
+        writeln!(f, "{} at position {}", self.message, column)?;
+        writeln!(f, "    {}", self.query)?;
+        write!(f, "    {}^", " ".repeat(column))
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryType {
+    Category(FileCategory),
+    Dir,
+}
+
+/// A date only as precise as it was written, `2010` matches any day of that year
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PartialDate {
+    pub year: i32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+
+impl PartialDate {
+    fn from_date_kind(date: &DateKind) -> Option<Self> {
+        match date {
+            DateKind::Chrono(date) => Some(Self {
+                year: date.year(),
+                month: Some(date.month()),
+                day: Some(date.day()),
+            }),
+            DateKind::Sym(date) => date.parse().ok(),
+        }
+    }
+
+    /// Compares down to the precision both sides share, `None` when `self` is too coarse
+    fn compare(&self, query: &Self) -> Option<Ordering> {
+        let mut ordering = self.year.cmp(&query.year);
+        for &(own, wanted) in &[(self.month, query.month), (self.day, query.day)] {
+            if ordering != Ordering::Equal {
+                break;
+            }
+            match (own, wanted) {
+                (Some(own), Some(wanted)) => ordering = own.cmp(&wanted),
+                (None, Some(_)) => return None,
+                (_, None) => break,
+            }
+        }
+        Some(ordering)
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = ();
+
+    fn from_str(date: &str) -> Result<Self, ()> {
+        let mut parts = date.splitn(3, '-');
+        let year = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        let month = parts.next().map(str::parse).transpose().map_err(|_| ())?;
+        let day = parts.next().map(str::parse).transpose().map_err(|_| ())?;
+
+        let (m, d) = (month.unwrap_or(1), day.unwrap_or(1));
+        NaiveDate::from_ymd_opt(year, m, d).ok_or(())?;
+        Ok(Self { year, month, day })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Predicate {
+    /// bare word, matched against file name, title, author and extras
+    Word(String),
+    Type(QueryType),
+    /// case insensitive substring of a text field
+    Text(&'static str, String),
+    Ext(String),
+    Lang(String),
+    Duration(Cmp, Duration),
+    Date(Cmp, PartialDate),
+    Size(Cmp, u64),
+    Loc(Cmp, u64),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub predicate: Predicate,
+}
+
+/// Clauses that must all hold, e.g. `type:audio author:"Daft Punk" duration>3m -live`
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+/// Fields a query can be evaluated against, on top of the template ones
+pub trait QueryRecord: TemplateRecord {
+    fn query_type(&self) -> Option<QueryType>;
+    fn languages(&self) -> Vec<&str>;
+    fn size(&self) -> Option<u64>;
+}
+
+impl QueryRecord for MediaMeta {
+    fn query_type(&self) -> Option<QueryType> {
+        let ext = get_file_ext(&self.file_path)?;
+        FileExt::from(ext).category().map(QueryType::Category)
+    }
+
+    fn languages(&self) -> Vec<&str> {
+        self.stats
+            .iter()
+            .flatten()
+            .map(|stats| stats.name.as_str())
+            .collect()
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.file_path
+            .metadata()
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+}
+
+impl QueryRecord for DirMeta {
+    fn query_type(&self) -> Option<QueryType> {
+        Some(QueryType::Dir)
+    }
+
+    fn languages(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.disk_size)
+    }
+}
+
+fn text_value(record: &dyn QueryRecord, field: &str) -> Option<String> {
+    match record.template_value(field)? {
+        TemplateValue::Text(text) => Some(text.to_lowercase()),
+        _ => None,
+    }
+}
+
+impl Predicate {
+    fn matches(&self, record: &dyn QueryRecord) -> bool {
+        match self {
+            Self::Word(word) => ["file_name", "title", "author", "extra"]
+                .iter()
+                .filter_map(|field| text_value(record, field))
+                .any(|text| text.contains(word.as_str())),
+            Self::Type(kind) => record.query_type() == Some(*kind),
+            Self::Text(field, wanted) => text_value(record, field)
+                .filter(|text| text.contains(wanted.as_str()))
+                .is_some(),
+            Self::Ext(wanted) => text_value(record, "path")
+                .and_then(|path| get_file_ext(Path::new(&path)).map(|ext| ext == wanted))
+                .unwrap_or(false),
+            Self::Lang(wanted) => record
+                .languages()
+                .iter()
+                .any(|lang| lang.eq_ignore_ascii_case(wanted)),
+            Self::Duration(cmp, wanted) => match record.template_value("duration") {
+                Some(TemplateValue::Duration(duration)) => cmp.holds(duration.cmp(wanted)),
+                _ => false,
+            },
+            Self::Date(cmp, wanted) => match record.template_value("date") {
+                Some(TemplateValue::Date(date)) => PartialDate::from_date_kind(&date)
+                    .and_then(|date| date.compare(wanted))
+                    .filter(|ordering| cmp.holds(*ordering))
+                    .is_some(),
+                _ => false,
+            },
+            Self::Size(cmp, wanted) => record
+                .size()
+                .filter(|size| cmp.holds(size.cmp(wanted)))
+                .is_some(),
+            Self::Loc(cmp, wanted) => match record.template_value("loc") {
+                Some(TemplateValue::Int(loc)) => cmp.holds(loc.cmp(wanted)),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Query {
+    pub fn matches(&self, record: &dyn QueryRecord) -> bool {
+        self.clauses
+            .iter()
+            .all(|clause| clause.predicate.matches(record) != clause.negated)
+    }
+
+    /// Non negated bare words, for narrowing down candidates with full text search first
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.clauses
+            .iter()
+            .filter_map(|clause| match clause.predicate {
+                Predicate::Word(ref word) if !clause.negated => Some(word.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Every clause except the ones returned by `words`
+    pub fn matches_fields(&self, record: &dyn QueryRecord) -> bool {
+        self.clauses
+            .iter()
+            .filter(|clause| clause.negated || !matches!(clause.predicate, Predicate::Word(_)))
+            .all(|clause| clause.predicate.matches(record) != clause.negated)
+    }
+}
+
+struct Parser<'q> {
+    query: &'q str,
+    pos: usize,
+}
+
+fn parse_duration(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let (mut secs, mut number) = (0, String::new());
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' if !number.is_empty() => {
+                let n: u64 = number.parse().ok()?;
+                secs += n * match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    number.is_empty().then(|| Duration::from_secs(secs))
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let bytes = byte_unit::Byte::from_str(value).ok()?.get_bytes();
+    Some(bytes as u64)
+}
+
+impl<'q> Parser<'q> {
+    fn error(&self, position: usize, message: impl Into<String>) -> QueryError {
+        QueryError {
+            query: self.query.to_owned(),
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.query[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        let matched = self.rest().starts_with(prefix);
+        if matched {
+            self.pos += prefix.len();
+        }
+        matched
+    }
+
+    /// A quoted string or everything up to the next whitespace
+    fn value(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        if self.eat("\"") {
+            let closing = self
+                .rest()
+                .find('"')
+                .ok_or_else(|| self.error(start, "unterminated quote"))?;
+            let value = &self.rest()[..closing];
+            self.pos += closing + 1;
+            return Ok(value.to_owned());
+        }
+
+        let end = self
+            .rest()
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest().len());
+        let value = &self.rest()[..end];
+        self.pos += end;
+        Ok(value.to_owned())
+    }
+
+    fn field(&self) -> Option<&'q str> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+        let next = rest[end..].chars().next()?;
+        (end > 0 && ":=<>".contains(next)).then(|| &rest[..end])
+    }
+
+    fn cmp(&mut self) -> Cmp {
+        for &(op, cmp) in &[
+            ("<=", Cmp::Le),
+            (">=", Cmp::Ge),
+            ("<", Cmp::Lt),
+            (">", Cmp::Gt),
+            (":", Cmp::Eq),
+            ("=", Cmp::Eq),
+        ] {
+            if self.eat(op) {
+                return cmp;
+            }
+        }
+        unreachable!("fields are only recognized before an operator")
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, QueryError> {
+        let field_start = self.pos;
+        let field = match self.field() {
+            Some(field) => field,
+            None => {
+                let word = self.value()?.to_lowercase();
+                return Ok(Predicate::Word(word));
+            }
+        };
+        self.pos += field.len();
+
+        let op_start = self.pos;
+        let cmp = self.cmp();
+        let value_start = self.pos;
+        let value = self.value()?;
+        if value.is_empty() {
+            return Err(self.error(value_start, format!("missing value for `{}`", field)));
+        }
+
+        let invalid = |what: &str| self.error(value_start, format!("invalid {} `{}`", what, value));
+        let ordered = matches!(field, "duration" | "date" | "size" | "loc");
+        if cmp != Cmp::Eq && !ordered {
+            return Err(self.error(op_start, format!("`{}` can't be compared, use `:`", field)));
+        }
+
+        let text = |field: &'static str| Predicate::Text(field, value.to_lowercase());
+        let predicate = match field {
+            "type" => match value.to_lowercase().as_str() {
+                "dir" => Predicate::Type(QueryType::Dir),
+                category => Predicate::Type(QueryType::Category(
+                    category.parse().map_err(|_| invalid("type"))?,
+                )),
+            },
+            "name" => text("file_name"),
+            "title" => text("title"),
+            "author" => text("author"),
+            "path" => text("path"),
+            "extra" => text("extra"),
+            "ext" => Predicate::Ext(value.trim_start_matches('.').to_lowercase()),
+            "lang" => Predicate::Lang(value),
+            "duration" => Predicate::Duration(
+                cmp,
+                parse_duration(&value).ok_or_else(|| invalid("duration"))?,
+            ),
+            "date" => Predicate::Date(cmp, value.parse().map_err(|_| invalid("date"))?),
+            "size" => Predicate::Size(cmp, parse_size(&value).ok_or_else(|| invalid("size"))?),
+            "loc" => Predicate::Loc(cmp, value.parse().map_err(|_| invalid("line count"))?),
+            _ => {
+                return Err(self.error(
+                    field_start,
+                    format!(
+                        "unknown field `{}`, expected one of: {}",
+                        field,
+                        FIELDS.join(", ")
+                    ),
+                ))
+            }
+        };
+        Ok(predicate)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser { query, pos: 0 };
+        let mut clauses = Vec::new();
+
+        loop {
+            parser.skip_whitespace();
+            if parser.rest().is_empty() {
+                break;
+            }
+
+            let negated = parser.eat("-");
+            let start = parser.pos;
+            let predicate = parser.predicate()?;
+            if predicate == Predicate::Word(String::new()) {
+                return Err(parser.error(start, "expected a word or field after `-`"));
+            }
+            clauses.push(Clause { negated, predicate });
+        }
+        Ok(Self { clauses })
+    }
+}
+
+#[test]
+fn parses_and_evaluates_queries() -> Result<()> {
+    let query: Query = r#"type:audio author:"daft punk" duration>3m date<2010 -live"#.parse()?;
+    assert_eq!(query.clauses.len(), 5);
+    assert_eq!(
+        query.clauses[2].predicate,
+        Predicate::Duration(Cmp::Gt, Duration::from_secs(180))
+    );
+
+    let mut meta = MediaMeta {
+        file_path: "/music/one more time.mp3".into(),
+        file_name: "one more time.mp3".to_owned(),
+        author: Some("Daft Punk".to_owned()),
+        duration: Some(Duration::from_secs(320)),
+        date: Some(DateKind::Sym("2000".to_owned())),
+        ..Default::default()
+    };
+    assert!(query.matches(&meta));
+
+    meta.title = Some("One More Time (Live)".to_owned());
+    assert!(!query.matches(&meta));
+
+    let dir = DirMeta {
+        path: "/music".into(),
+        disk_size: 2 * 1024 * 1024 * 1024,
+        num_files: 3,
+    };
+    assert!("type:dir size>1GB".parse::<Query>()?.matches(&dir));
+    assert!(!"size>1GB name:tunes".parse::<Query>()?.matches(&dir));
+    Ok(())
+}
+
+#[test]
+fn points_at_query_errors() {
+    let error = "type:audio autor:x".parse::<Query>().unwrap_err();
+    assert_eq!(error.position, 11);
+    assert!(error.to_string().ends_with("\n               ^"));
+
+    for (query, position) in &[
+        ("duration>3x", 9),
+        ("title<x", 5),
+        ("author:\"daft", 7),
+        ("type:pdf", 5),
+        ("date:", 5),
+    ] {
+        let error = query.parse::<Query>().unwrap_err();
+        assert_eq!(error.position, *position, "{}", query);
+    }
+}
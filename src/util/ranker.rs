@@ -0,0 +1,304 @@
+use crate::{
+    prelude::*,
+    util::{file_types::FileCategory, index::SearchHit},
+};
+use std::{
+    collections::VecDeque,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tempfile::NamedTempFile;
+
+const RANKER_DIR: &str = "buo";
+const RANKER_FILE: &str = "ranker.json";
+
+pub const FEATURES: usize = 10;
+const HIDDEN: usize = 8;
+
+const LEARNING_RATE: f64 = 0.05;
+const EPOCHS: usize = 40;
+/// Predictions are only trusted once this much feedback was collected
+const MIN_SAMPLES: usize = 20;
+/// Feedback kept for retraining, oldest samples are dropped first
+const MAX_SAMPLES: usize = 512;
+/// Skipped results recorded per accepted one, so long lists don't drown the signal
+pub const MAX_SKIPPED: usize = 8;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+/// Days over which a file's modification time stops counting as recent
+const RECENCY_DAYS: f64 = 30.0;
+
+/// Location of the ranker model, creating its parent directory if needed
+pub fn default_ranker_path() -> Result<PathBuf> {
+    let mut ranker_path = dirs::data_dir().ok_or_else(|| anyhow!("Unable to locate data dir"))?;
+    ranker_path.push(RANKER_DIR);
+    std::fs::create_dir_all(&ranker_path)?;
+
+    ranker_path.push(RANKER_FILE);
+    Ok(ranker_path)
+}
+
+/// The first feature, computed the same way for `find` feedback, `find` priors and `search`:
+/// a result's match score relative to the best one in its list. Results of a list that wasn't
+/// matched against anything, without a query or searched words, all get 1.
+pub fn text_match(score: Option<f64>, best: f64) -> f64 {
+    match score {
+        Some(score) if best > 0.0 => (score / best).clamp(0.0, 1.0),
+        _ => 1.0,
+    }
+}
+
+/// Features of a result, every one of them scaled to roughly `0..=1`.
+/// `text_match` comes from the function of the same name.
+pub fn features(meta: &MediaMeta, text_match: f64, frecency: f64, now: i64) -> [f64; FEATURES] {
+    let category = get_file_ext(&meta.file_path).and_then(|ext| FileExt::from(ext).category());
+    let one_hot = |wanted: FileCategory| (category == Some(wanted)) as u8 as f64;
+
+    let recency = meta
+        .file_path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|since| {
+            let age_days = (now - since.as_secs() as i64).max(0) as f64 / SECS_PER_DAY;
+            (-age_days / RECENCY_DAYS).exp()
+        })
+        .unwrap_or_default();
+    let depth = meta.file_path.components().count() as f64;
+
+    [
+        text_match,
+        frecency.ln_1p() / (1.0 + frecency.ln_1p()),
+        recency,
+        1.0 / (1.0 + depth),
+        one_hot(FileCategory::Audio),
+        one_hot(FileCategory::Video),
+        one_hot(FileCategory::Dev),
+        one_hot(FileCategory::Text),
+        (meta.title.is_some() || meta.author.is_some()) as u8 as f64,
+        meta.duration.is_some() as u8 as f64,
+    ]
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Sample {
+    pub features: [f64; FEATURES],
+    pub accepted: bool,
+}
+
+/// Two layer perceptron predicting how likely a result is to be picked
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Ranker {
+    hidden_weights: Vec<Vec<f64>>,
+    hidden_bias: Vec<f64>,
+    output_weights: Vec<f64>,
+    output_bias: f64,
+    samples: VecDeque<Sample>,
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Deterministic xorshift, so training runs are reproducible without a rng dependency
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `-scale..scale`
+    fn uniform(&mut self, scale: f64) -> f64 {
+        (self.next() as f64 / u64::MAX as f64 * 2.0 - 1.0) * scale
+    }
+}
+
+impl Default for Ranker {
+    fn default() -> Self {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let scale = 1.0 / (FEATURES as f64).sqrt();
+
+        Self {
+            hidden_weights: (0..HIDDEN)
+                .map(|_| (0..FEATURES).map(|_| rng.uniform(scale)).collect())
+                .collect(),
+            hidden_bias: vec![0.0; HIDDEN],
+            output_weights: (0..HIDDEN).map(|_| rng.uniform(scale)).collect(),
+            output_bias: 0.0,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl Ranker {
+    /// Loads the model at `path`, starting untrained if there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = read_to_string(path)?;
+        let ranker: Self = serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid ranker model {}: {}", path.display(), e))?;
+
+        let shaped = ranker.hidden_weights.len() == HIDDEN
+            && ranker
+                .hidden_weights
+                .iter()
+                .all(|row| row.len() == FEATURES)
+            && ranker.hidden_bias.len() == HIDDEN
+            && ranker.output_weights.len() == HIDDEN;
+        if !shaped {
+            bail!(
+                "Ranker model {} doesn't match this version of buo, remove it to start over",
+                path.display()
+            );
+        }
+        Ok(ranker)
+    }
+
+    /// Writes a temporary file next to `path` and renames it over the model, so an interrupted
+    /// or concurrent `find` never leaves a truncated model behind
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let mut file = NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&mut file, self)?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        Ok(())
+    }
+
+    pub fn is_trained(&self) -> bool {
+        self.samples.len() >= MIN_SAMPLES
+    }
+
+    fn forward(&self, features: &[f64; FEATURES]) -> (Vec<f64>, f64) {
+        let hidden: Vec<f64> = self
+            .hidden_weights
+            .iter()
+            .zip(&self.hidden_bias)
+            .map(|(weights, bias)| {
+                let sum: f64 = weights.iter().zip(features).map(|(w, x)| w * x).sum();
+                (sum + bias).tanh()
+            })
+            .collect();
+
+        let sum: f64 = self
+            .output_weights
+            .iter()
+            .zip(&hidden)
+            .map(|(w, h)| w * h)
+            .sum();
+        (hidden, sigmoid(sum + self.output_bias))
+    }
+
+    /// Probability of the result being picked
+    pub fn predict(&self, features: &[f64; FEATURES]) -> f64 {
+        self.forward(features).1
+    }
+
+    /// One gradient descent step on the binary cross entropy of a sample
+    fn step(&mut self, Sample { features, accepted }: &Sample) {
+        let (hidden, predicted) = self.forward(features);
+        let output_grad = predicted - (*accepted as u8 as f64);
+
+        for (j, h) in hidden.iter().enumerate() {
+            let hidden_grad = output_grad * self.output_weights[j] * (1.0 - h * h);
+            self.output_weights[j] -= LEARNING_RATE * output_grad * h;
+
+            for (w, x) in self.hidden_weights[j].iter_mut().zip(features) {
+                *w -= LEARNING_RATE * hidden_grad * x;
+            }
+            self.hidden_bias[j] -= LEARNING_RATE * hidden_grad;
+        }
+        self.output_bias -= LEARNING_RATE * output_grad;
+    }
+
+    /// Adds feedback and retrains on everything collected so far
+    pub fn learn(&mut self, samples: impl IntoIterator<Item = Sample>) {
+        self.samples.extend(samples);
+        while self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        let mut rng = XorShift(self.samples.len() as u64 | 1);
+        let mut order: Vec<usize> = (0..self.samples.len()).collect();
+        for _ in 0..EPOCHS {
+            for i in (1..order.len()).rev() {
+                order.swap(i, rng.next() as usize % (i + 1));
+            }
+            for &i in &order {
+                let sample = self.samples[i].clone();
+                self.step(&sample);
+            }
+        }
+    }
+
+    /// Scores hits with the probability of being picked, keeping the heuristic order until
+    /// the model is trained
+    pub fn rank_hits(&self, hits: &mut [SearchHit], now: i64) {
+        if !self.is_trained() {
+            return;
+        }
+
+        let best = hits
+            .iter()
+            .filter_map(|hit| hit.relevance)
+            .fold(0.0, f64::max);
+        for hit in hits.iter_mut() {
+            let text_match = text_match(hit.relevance, best);
+            hit.score = self.predict(&features(&hit.meta, text_match, hit.frecency, now));
+        }
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+}
+
+#[test]
+fn learns_from_feedback() {
+    let sample = |frecency: f64, accepted| {
+        let mut features = [0.0; FEATURES];
+        features[0] = 0.5;
+        features[1] = frecency;
+        Sample { features, accepted }
+    };
+
+    let mut ranker = Ranker::default();
+    assert!(!ranker.is_trained());
+
+    ranker.learn((0..MIN_SAMPLES).map(|i| sample((i % 2) as f64, i % 2 == 1)));
+    assert!(ranker.is_trained());
+    assert!(ranker.predict(&sample(1.0, true).features) > 0.8);
+    assert!(ranker.predict(&sample(0.0, false).features) < 0.2);
+}
+
+#[test]
+fn saves_atomically() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("ranker.json");
+
+    let mut ranker = Ranker::default();
+    ranker.learn(vec![Sample {
+        features: [0.5; FEATURES],
+        accepted: true,
+    }]);
+    ranker.save(&path)?;
+    ranker.save(&path)?;
+
+    assert_eq!(Ranker::load(&path)?.samples.len(), 1);
+    // the temporary files were renamed into place
+    assert_eq!(std::fs::read_dir(tmp.path())?.count(), 1);
+    Ok(())
+}
@@ -0,0 +1,457 @@
+use crate::{
+    prelude::*,
+    util::{
+        dirs::human_readable_bytes, dupes::DupeGroup, index::SearchHit, json_out::TargetIssue,
+        web::WebBookmark,
+    },
+};
+use chrono::format::{Item, StrftimeItems};
+use std::{iter::Peekable, str::CharIndices, time::Duration};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    Text,
+    Int,
+    Duration,
+    Date,
+}
+
+const FIELDS: &[(&str, FieldKind)] = &[
+    ("type", FieldKind::Text),
+    ("status", FieldKind::Text),
+    ("path", FieldKind::Text),
+    ("file_name", FieldKind::Text),
+    ("title", FieldKind::Text),
+    ("author", FieldKind::Text),
+    ("duration", FieldKind::Duration),
+    ("date", FieldKind::Date),
+    ("extra", FieldKind::Text),
+    ("languages", FieldKind::Text),
+    ("loc", FieldKind::Int),
+    ("comments", FieldKind::Int),
+    ("disk_size", FieldKind::Int),
+    ("num_files", FieldKind::Int),
+    ("url", FieldKind::Text),
+    ("message", FieldKind::Text),
+    ("snippet", FieldKind::Text),
+    ("hash", FieldKind::Text),
+    ("size", FieldKind::Int),
+    ("reclaimable", FieldKind::Int),
+    ("paths", FieldKind::Text),
+];
+
+/// A typed field value, kept typed until filters are applied
+pub enum TemplateValue {
+    Text(String),
+    Int(u64),
+    Duration(Duration),
+    Date(DateKind),
+}
+
+impl TemplateValue {
+    fn is_empty(&self) -> bool {
+        matches!(self, Self::Text(text) if text.is_empty())
+    }
+}
+
+fn format_min_sec(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+use std::fmt;
+impl fmt::Display for TemplateValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{}", text),
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Duration(duration) => write!(f, "{}", format_min_sec(duration)),
+            Self::Date(DateKind::Chrono(date)) => write!(f, "{}", date.to_rfc3339()),
+            Self::Date(DateKind::Sym(year)) => write!(f, "{}", year),
+        }
+    }
+}
+
+pub trait TemplateRecord {
+    fn template_value(&self, field: &str) -> Option<TemplateValue>;
+}
+
+#[derive(Debug, PartialEq)]
+enum Filter {
+    /// human readable byte size
+    Bytes,
+    /// duration as minutes and seconds
+    MinSec,
+    /// duration as whole seconds
+    Secs,
+    /// strftime style date formatting
+    Date(String),
+    Upper,
+    Lower,
+    /// fallback text for a missing field
+    Default(String),
+}
+
+impl Filter {
+    fn parse(name: &str, arg: Option<String>, kind: FieldKind) -> Result<Self> {
+        let filter = match (name, arg) {
+            ("bytes", None) if kind == FieldKind::Int => Self::Bytes,
+            ("mmss", None) if kind == FieldKind::Duration => Self::MinSec,
+            ("secs", None) if kind == FieldKind::Duration => Self::Secs,
+            ("date", Some(fmt)) if kind == FieldKind::Date => {
+                // chrono only reports a bad spec when formatting, which would panic on render
+                if StrftimeItems::new(&fmt).any(|item| item == Item::Error) {
+                    bail!("invalid date format `{}`", fmt);
+                }
+                Self::Date(fmt)
+            }
+            ("upper", None) => Self::Upper,
+            ("lower", None) => Self::Lower,
+            ("default", Some(fallback)) => Self::Default(fallback),
+            ("bytes", None) | ("mmss", None) | ("secs", None) | ("date", Some(_)) => {
+                bail!("filter `{}` does not apply to this field", name)
+            }
+            (name, _) => bail!("unknown filter or missing argument: `{}`", name),
+        };
+        Ok(filter)
+    }
+
+    fn apply(&self, value: Option<TemplateValue>) -> Option<TemplateValue> {
+        use TemplateValue::*;
+
+        let value = match (self, value) {
+            (Self::Default(fallback), None) => return Some(Text(fallback.clone())),
+            (_, None) => return None,
+            (_, Some(value)) => value,
+        };
+
+        let filtered = match (self, value) {
+            (Self::Bytes, Int(n)) => Text(human_readable_bytes(n)),
+            (Self::MinSec, Duration(duration)) => Text(format_min_sec(&duration)),
+            (Self::Secs, Duration(duration)) => Int(duration.as_secs()),
+            (Self::Date(fmt), Date(DateKind::Chrono(date))) => Text(date.format(fmt).to_string()),
+            (Self::Upper, value) => Text(value.to_string().to_uppercase()),
+            (Self::Lower, value) => Text(value.to_string().to_lowercase()),
+            (_, value) => value,
+        };
+        Some(filtered)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Placeholder {
+    field: &'static str,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field(Placeholder),
+    /// only rendered when every field inside it has a value
+    Section(Vec<Segment>),
+}
+
+/// An output template such as `{file_name}\t[{author} - ]{title} ({duration|mmss})`.
+///
+/// Fields are written as `{field}` and may be piped through filters,
+/// `{field|filter:arg}`. Text inside `[...]` is dropped unless every field in it
+/// has a value. `{{`, `}}`, `[[` and `]]` produce literal braces and brackets,
+/// and `\t`, `\n` and `\\` are unescaped.
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+struct TemplateParser<'t> {
+    chars: Peekable<CharIndices<'t>>,
+}
+
+impl<'t> TemplateParser<'t> {
+    fn parse_segments(&mut self, in_section: bool) -> Result<Vec<Segment>> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+
+        while let Some((pos, c)) = self.chars.next() {
+            let escaped = match c {
+                '{' | '[' | '}' | ']' if self.chars.peek().map(|(_, next)| *next) == Some(c) => {
+                    self.chars.next();
+                    Some(c)
+                }
+                '\\' => match self.chars.next() {
+                    Some((_, 't')) => Some('\t'),
+                    Some((_, 'n')) => Some('\n'),
+                    Some((_, '\\')) => Some('\\'),
+                    Some((_, other)) => {
+                        literal.push('\\');
+                        Some(other)
+                    }
+                    None => Some('\\'),
+                },
+                _ => None,
+            };
+
+            if let Some(escaped) = escaped {
+                literal.push(escaped);
+                continue;
+            }
+
+            match c {
+                '{' | '[' | ']' if !literal.is_empty() => {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                _ => {}
+            }
+
+            match c {
+                '{' => segments.push(Segment::Field(self.parse_placeholder(pos)?)),
+                '[' => segments.push(Segment::Section(self.parse_segments(true)?)),
+                ']' if in_section => return Ok(segments),
+                ']' => bail!("unmatched `]` at position {}", pos),
+                '}' => bail!("unmatched `}}` at position {}", pos),
+                c => literal.push(c),
+            }
+        }
+
+        if in_section {
+            bail!("unclosed `[` section");
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(segments)
+    }
+
+    fn parse_placeholder(&mut self, start: usize) -> Result<Placeholder> {
+        let mut body = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, c)) => body.push(c),
+                None => bail!("unclosed `{{` at position {}", start),
+            }
+        }
+
+        let mut parts = body.split('|');
+        let name = parts.next().unwrap_or_default().trim();
+        let &(field, kind) = FIELDS
+            .iter()
+            .find(|(field, _)| *field == name)
+            .ok_or_else(|| {
+                let fields: Vec<_> = FIELDS.iter().map(|(field, _)| *field).collect();
+                anyhow!(
+                    "unknown field `{}` at position {}, expected one of: {}",
+                    name,
+                    start,
+                    fields.join(", ")
+                )
+            })?;
+
+        let filters = parts
+            .map(|filter| {
+                let mut filter = filter.splitn(2, ':');
+                let name = filter.next().unwrap_or_default().trim();
+                let arg = filter.next().map(|arg| arg.to_owned());
+                Filter::parse(name, arg, kind)
+            })
+            .collect::<Result<_>>()
+            .map_err(|e| anyhow!("{} in `{{{}}}` at position {}", e, body, start))?;
+
+        Ok(Placeholder { field, filters })
+    }
+}
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        let mut parser = TemplateParser {
+            chars: template.char_indices().peekable(),
+        };
+        let segments = parser.parse_segments(false)?;
+        Ok(Self { segments })
+    }
+}
+
+/// Renders segments into `out`, returning false if any field was missing
+fn render_segments(
+    segments: &[Segment],
+    resolve: &dyn Fn(&str) -> Option<TemplateValue>,
+    out: &mut String,
+) -> bool {
+    let mut complete = true;
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => out.push_str(literal),
+            Segment::Field(Placeholder { field, filters }) => {
+                let value = resolve(field).filter(|value| !value.is_empty());
+                match filters
+                    .iter()
+                    .fold(value, |value, filter| filter.apply(value))
+                {
+                    Some(value) => out.push_str(&value.to_string()),
+                    None => complete = false,
+                }
+            }
+            Segment::Section(section) => {
+                let mut rendered = String::new();
+                if render_segments(section, resolve, &mut rendered) {
+                    out.push_str(&rendered);
+                }
+            }
+        }
+    }
+    complete
+}
+
+impl Template {
+    pub fn render(&self, resolve: &dyn Fn(&str) -> Option<TemplateValue>) -> String {
+        let mut out = String::new();
+        render_segments(&self.segments, resolve, &mut out);
+        out
+    }
+}
+
+impl TemplateRecord for MediaMeta {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        use TemplateValue::*;
+
+        let stats = self.stats.as_ref();
+        let value = match field {
+            "path" => Text(self.file_path.to_string_lossy().to_string()),
+            "file_name" => Text(self.file_name.clone()),
+            "title" => Text(self.title.clone()?),
+            "author" => Text(self.author.clone()?),
+            "duration" => Duration(self.duration?),
+            "date" => Date(self.date.clone()?),
+            "extra" => Text(self.extra.clone()?),
+            "languages" => Text(
+                stats?
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            "loc" => Int(stats?
+                .iter()
+                .filter_map(|s| s.loc)
+                .map(|n| n.get() as u64)
+                .sum()),
+            "comments" => Int(stats?
+                .iter()
+                .filter_map(|s| s.comments)
+                .map(|n| n.get() as u64)
+                .sum()),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+impl TemplateRecord for DirMeta {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        use TemplateValue::*;
+
+        let value = match field {
+            "path" => Text(self.path.to_string_lossy().to_string()),
+            "file_name" => Text(get_file_name(&self.path)),
+            "disk_size" => Int(self.disk_size),
+            "num_files" => Int(self.num_files),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+impl TemplateRecord for WebBookmark {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        match field {
+            "title" => self.name.clone().map(TemplateValue::Text),
+            "url" | "path" => Some(TemplateValue::Text(self.url.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl TemplateRecord for TargetIssue {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        match field {
+            "path" => Some(TemplateValue::Text(
+                self.file_path.to_string_lossy().to_string(),
+            )),
+            "file_name" => Some(TemplateValue::Text(get_file_name(&self.file_path))),
+            "message" => Some(TemplateValue::Text(self.message.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl TemplateRecord for SearchHit {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        match field {
+            "snippet" => self.snippet.clone().map(TemplateValue::Text),
+            _ => self.meta.template_value(field),
+        }
+    }
+}
+
+impl TemplateRecord for DupeGroup {
+    fn template_value(&self, field: &str) -> Option<TemplateValue> {
+        use TemplateValue::*;
+
+        let value = match field {
+            // the copy kept when the others are removed
+            "path" => Text(self.paths.first()?.to_string_lossy().to_string()),
+            "paths" => Text(
+                self.paths
+                    .iter()
+                    .map(|path| path.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            "hash" => Text(self.hash.clone()),
+            "size" => Int(self.size),
+            "disk_size" => Int(self.disk_size),
+            "num_files" => Int(self.paths.len() as u64),
+            "reclaimable" => Int(self.reclaimable),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[test]
+fn renders_sections_and_filters() -> Result<()> {
+    let template: Template = "{file_name}\\t[{author} - ]{title} ({duration|mmss})".parse()?;
+    let meta = MediaMeta {
+        file_name: "track.flac".into(),
+        title: Some("Around the World".into()),
+        duration: Some(Duration::from_secs(429)),
+        ..Default::default()
+    };
+
+    let rendered = template.render(&|field| meta.template_value(field));
+    assert_eq!(rendered, "track.flac\tAround the World (7:09)");
+
+    let dir = DirMeta {
+        path: "/music".into(),
+        disk_size: 2048,
+        num_files: 3,
+    };
+    let template: Template = "{{{num_files}}} {disk_size|bytes}[ {title|upper}]".parse()?;
+    assert_eq!(
+        template.render(&|field| dir.template_value(field)),
+        "{3} 2.05 KB"
+    );
+    Ok(())
+}
+
+#[test]
+fn rejects_invalid_templates() {
+    assert!("{nope}".parse::<Template>().is_err());
+    assert!("{title|bytes}".parse::<Template>().is_err());
+    assert!("[{title}".parse::<Template>().is_err());
+    assert!("{title".parse::<Template>().is_err());
+    assert!("{date|date:%Q}".parse::<Template>().is_err());
+    assert!("{date|date:%Y-%m-%d}".parse::<Template>().is_ok());
+}
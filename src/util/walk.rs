@@ -0,0 +1,248 @@
+use crate::prelude::*;
+use globset::GlobSet;
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use std::{
+    collections::HashMap,
+    env::current_dir,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Read in every walked directory, rules of the later file win within a directory
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".buoignore"];
+const GIT_DIR: &str = ".git";
+
+/// A path is ignored if the whole path or any of its components match
+fn matches_globs(globs: &GlobSet, path: &Path) -> bool {
+    !globs.is_empty()
+        && (globs.is_match(path)
+            || path
+                .components()
+                .any(|component| globs.is_match(component.as_os_str())))
+}
+
+/// Decides which paths directory walks skip: config globs, `.git` directories, and the rules of
+/// `.gitignore` and `.buoignore` files in walked directories and their parents up to the
+/// enclosing git repository. Ignore files are parsed and repositories located once, and shared
+/// across threads.
+pub struct Ignorer {
+    globs: GlobSet,
+    read_files: bool,
+    cwd: PathBuf,
+    /// parsed ignore files by directory, `None` for directories without any
+    dir_rules: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+    /// topmost directory whose ignore files apply, by walk root
+    boundaries: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl Ignorer {
+    pub fn new(globs: GlobSet, read_files: bool) -> Self {
+        Self {
+            globs,
+            read_files,
+            cwd: current_dir().unwrap_or_default(),
+            dir_rules: Mutex::new(HashMap::new()),
+            boundaries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Ignores nothing, for `--no-ignore`
+    pub fn none() -> Self {
+        Self::new(GlobSet::empty(), false)
+    }
+
+    /// The enclosing git repository of `root`, or `root` itself outside of one, since then only
+    /// the walked tree's own ignore files apply
+    fn boundary_of(&self, root: &Path) -> PathBuf {
+        let mut boundaries = self.boundaries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(boundary) = boundaries.get(root) {
+            return boundary.clone();
+        }
+
+        let boundary = root
+            .ancestors()
+            .find(|dir| dir.join(GIT_DIR).exists())
+            .unwrap_or(root)
+            .to_path_buf();
+        boundaries.insert(root.to_path_buf(), boundary.clone());
+        boundary
+    }
+
+    /// Parses the ignore files of `dir` on first use
+    fn rules_of(
+        dir_rules: &mut HashMap<PathBuf, Option<Arc<Gitignore>>>,
+        dir: &Path,
+    ) -> Option<Arc<Gitignore>> {
+        if let Some(rules) = dir_rules.get(dir) {
+            return rules.clone();
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in &IGNORE_FILES {
+            let file = dir.join(name);
+            if file.is_file() {
+                found = true;
+                if let Some(e) = builder.add(&file) {
+                    eprintln!("{}: {}", file.display(), e);
+                }
+            }
+        }
+        let rules = Some(builder)
+            .filter(|_| found)
+            .and_then(|builder| builder.build().ok())
+            .map(Arc::new);
+        dir_rules.insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+
+    /// Forgets the parsed ignore files of `dir`, after one of them changed
+    pub fn forget(&self, dir: &Path) {
+        let mut dir_rules = self.dir_rules.lock().unwrap_or_else(|e| e.into_inner());
+        dir_rules.remove(&self.cwd.join(dir));
+    }
+
+    /// Whether a walk started at `root` skips `path`. Walks never descend into ignored
+    /// directories, so only the path itself is matched and not its parents.
+    pub fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        if matches_globs(&self.globs, path.strip_prefix(root).unwrap_or(path)) {
+            return true;
+        }
+        if !self.read_files {
+            return false;
+        }
+        if path.file_name() == Some(GIT_DIR.as_ref()) {
+            return true;
+        }
+
+        let path = self.cwd.join(path);
+        let boundary = self.boundary_of(&self.cwd.join(root));
+
+        let mut dir_rules = self.dir_rules.lock().unwrap_or_else(|e| e.into_inner());
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&boundary) {
+                break;
+            }
+            let rules = match Self::rules_of(&mut dir_rules, dir) {
+                Some(rules) => rules,
+                None => continue,
+            };
+
+            match rules.matched(path.strip_prefix(dir).unwrap_or(&path), is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+/// Recursively accrues every regular file below `dir_path`.
+/// A `max_depth` of 0 only visits the files directly inside `dir_path`,
+/// and symlinked directories are never followed to avoid cycles.
+fn acc_files(
+    root: &Path,
+    dir_path: &Path,
+    acc: &mut Vec<PathBuf>,
+    depth: usize,
+    max_depth: Option<usize>,
+    ignorer: &Ignorer,
+) -> Result<()> {
+    let mut dir_ents = read_dir(dir_path)?
+        .filter_map(|ent| ent.ok())
+        .collect::<Vec<_>>();
+    dir_ents.sort_unstable_by_key(|ent| ent.file_name());
+
+    for ent in dir_ents {
+        let file_type = match ent.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+
+        let path = ent.path();
+        if ignorer.is_ignored(root, &path, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            if !matches!(max_depth, Some(max_depth) if depth >= max_depth) {
+                if let Err(e) = acc_files(root, &path, acc, depth + 1, max_depth, ignorer) {
+                    eprintln!("Skipping {}: {}", path.display(), e);
+                }
+            }
+        } else if path.is_file() {
+            acc.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Collects the files below `root_path` in a stable, sorted order
+pub fn collect_files(
+    root_path: &Path,
+    max_depth: Option<usize>,
+    ignorer: &Ignorer,
+) -> Result<Vec<PathBuf>> {
+    collect_files_below(root_path, root_path, max_depth, ignorer)
+}
+
+/// Like `collect_files` for a directory inside `root_path`,
+/// applying the same ignore rules a walk of the whole root would
+pub fn collect_files_below(
+    root_path: &Path,
+    dir_path: &Path,
+    max_depth: Option<usize>,
+    ignorer: &Ignorer,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    acc_files(root_path, dir_path, &mut files, 0, max_depth, ignorer)?;
+    Ok(files)
+}
+
+#[test]
+fn honours_ignore_files() -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let root = tmp.path();
+    for dir in &["node_modules/pkg", "src/gen", ".git", "keep"] {
+        std::fs::create_dir_all(root.join(dir))?;
+    }
+    for file in &[
+        "a.rs",
+        "b.log",
+        "node_modules/pkg/c.rs",
+        "src/d.rs",
+        "src/gen/e.rs",
+        "keep/f.log",
+        ".git/config",
+    ] {
+        std::fs::write(root.join(file), "buo")?;
+    }
+    std::fs::write(root.join(".gitignore"), "node_modules/\n*.log\n")?;
+    std::fs::write(root.join("keep/.buoignore"), "!f.log\n")?;
+    std::fs::write(root.join("src/.buoignore"), "gen\n")?;
+
+    let mut globs = globset::GlobSetBuilder::new();
+    globs.add(globset::Glob::new("a.*")?);
+    let walked = collect_files(root, None, &Ignorer::new(globs.build()?, true))?;
+    let everything = collect_files(root, None, &Ignorer::none())?;
+
+    let relative = |files: Vec<PathBuf>| -> Vec<PathBuf> {
+        files
+            .iter()
+            .filter_map(|file| file.strip_prefix(root).ok())
+            .filter(|file| !IGNORE_FILES.iter().any(|name| file.ends_with(name)))
+            .map(Path::to_path_buf)
+            .collect()
+    };
+    assert_eq!(
+        relative(walked),
+        vec![PathBuf::from("keep/f.log"), PathBuf::from("src/d.rs")]
+    );
+    assert_eq!(relative(everything).len(), 7);
+    Ok(())
+}
@@ -1,10 +1,60 @@
 pub mod chromium;
 pub mod firefox;
 
-use crate::prelude::*;
+use crate::{prelude::*, util::os::Os};
+use chromium::{get_chromium_bookmarks, resolve_chromium_browser_bookmark_path, ChromiumBrowser};
+use firefox::get_firefox_bookmarks;
+use strum::{AsRefStr, EnumIter, EnumString};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WebBookmark {
     pub name: Option<String>,
     pub url: String,
 }
+
+use std::fmt;
+impl fmt::Display for WebBookmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) if !name.is_empty() => write!(f, "{}\n{}", name, self.url),
+            _ => write!(f, "{}", self.url),
+        }
+    }
+}
+
+#[derive(
+    AsRefStr, Clone, Copy, Debug, Deserialize, EnumIter, EnumString, PartialEq, Eq, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum BookmarkSource {
+    Firefox,
+    Brave,
+    Chrome,
+    Chromium,
+    Edge,
+    Vivaldi,
+}
+
+impl BookmarkSource {
+    /// Reads bookmarks from `profile`, or the browser's default profile
+    pub fn fetch_bookmarks(self, profile: Option<&str>) -> Result<Vec<WebBookmark>> {
+        let os = Os::current().ok_or_else(|| anyhow!("Unsupported operating system"))?;
+
+        let chromium_browser = match self {
+            Self::Firefox => {
+                let home_dir =
+                    dirs::home_dir().ok_or_else(|| anyhow!("Unable to locate home dir"))?;
+                return get_firefox_bookmarks(&home_dir, profile);
+            }
+            Self::Brave => ChromiumBrowser::Brave,
+            Self::Chrome => ChromiumBrowser::Chrome,
+            Self::Chromium => ChromiumBrowser::Chromium,
+            Self::Edge => ChromiumBrowser::Edge,
+            Self::Vivaldi => ChromiumBrowser::Vivaldi,
+        };
+
+        let bookmark_path = resolve_chromium_browser_bookmark_path(chromium_browser, os, profile)?;
+        Ok(get_chromium_bookmarks(&bookmark_path)?.unwrap_or_default())
+    }
+}
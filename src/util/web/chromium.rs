@@ -14,7 +14,15 @@ pub enum ChromiumBrowser {
     Vivaldi,
 }
 
-fn with_default_profile(browser_dir: &mut PathBuf) -> Result<()> {
+fn with_default_profile(browser_dir: &mut PathBuf, profile: Option<&str>) -> Result<()> {
+    if let Some(profile) = profile {
+        browser_dir.push(profile);
+        if !browser_dir.is_dir() {
+            bail!("No browser profile named {}", profile);
+        }
+        return Ok(());
+    }
+
     browser_dir.push("Default");
     if browser_dir.exists() {
         return Ok(());
@@ -31,7 +39,11 @@ fn with_default_profile(browser_dir: &mut PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn resolve_chromium_browser_bookmark_path(browser: ChromiumBrowser, os: Os) -> Result<PathBuf> {
+pub fn resolve_chromium_browser_bookmark_path(
+    browser: ChromiumBrowser,
+    os: Os,
+    profile: Option<&str>,
+) -> Result<PathBuf> {
     use ChromiumBrowser::*;
     use Os::*;
 
@@ -39,16 +51,19 @@ pub fn resolve_chromium_browser_bookmark_path(browser: ChromiumBrowser, os: Os)
 
     match (browser, os) {
         (Brave, Linux) => bkmarks.push("BraveSoftware/Brave-Browser/"),
+        (Chrome, Linux) => bkmarks.push("google-chrome/"),
+        (Chromium, Linux) => bkmarks.push("chromium/"),
+        (Edge, Linux) => bkmarks.push("microsoft-edge/"),
         (Vivaldi, Linux) => bkmarks.push("vivaldi/"),
         (Brave, Windows) => bkmarks.push("Local/BraveSoftware/Brave-Browser/"),
         (Chrome, Windows) => bkmarks.push("Local/Google/Chrome/"),
         (Chromium, Windows) => bkmarks.push("Local/Google/Chromium/"),
         (Edge, Windows) => bkmarks.push("Local/Microsoft/Edge/User Data/"),
         (Vivaldi, Windows) => bkmarks.push("Local/Vivaldi/Application/"),
-        _ => todo!(),
+        _ => bail!("Bookmarks for this browser are not supported on this os"),
     };
 
-    with_default_profile(&mut bkmarks)?;
+    with_default_profile(&mut bkmarks, profile)?;
     bkmarks.push("Bookmarks");
     Ok(bkmarks)
 }
@@ -85,7 +100,8 @@ pub fn get_chromium_bookmarks(bookmark_path: &Path) -> Result<Option<Vec<WebBook
 
 #[test]
 fn brave_bookmarks() -> Result<()> {
-    let bookmarks_path = resolve_chromium_browser_bookmark_path(ChromiumBrowser::Brave, Os::Linux)?;
+    let bookmarks_path =
+        resolve_chromium_browser_bookmark_path(ChromiumBrowser::Brave, Os::Linux, None)?;
     let bookmarks = get_chromium_bookmarks(&bookmarks_path)?;
 
     match bookmarks {
@@ -102,7 +118,7 @@ fn brave_bookmarks() -> Result<()> {
 #[test]
 fn vivaldi_bookmarks() -> Result<()> {
     let bookmarks_path =
-        resolve_chromium_browser_bookmark_path(ChromiumBrowser::Vivaldi, Os::Linux)?;
+        resolve_chromium_browser_bookmark_path(ChromiumBrowser::Vivaldi, Os::Linux, None)?;
     let bookmarks = get_chromium_bookmarks(&bookmarks_path)?;
 
     match bookmarks {
@@ -12,7 +12,15 @@ const FF_ROOT_PATH: &str = ".mozilla/firefox";
 const PLACES: &str = "places.sqlite";
 const QUERY: &str = "SELECT b.title, p.url FROM moz_bookmarks b JOIN moz_places p ON p.id = b.fk";
 
-fn with_default_firefox_profile(firefox_dir: &Path) -> Result<PathBuf> {
+fn with_default_firefox_profile(firefox_dir: &Path, profile: Option<&str>) -> Result<PathBuf> {
+    if let Some(profile) = profile {
+        let profile_path = firefox_dir.join(profile);
+        if !profile_path.is_dir() {
+            bail!("No firefox profile named {}", profile);
+        }
+        return Ok(profile_path);
+    }
+
     for ent in read_dir(firefox_dir)? {
         let path = ent?.path();
         if path.to_string_lossy().ends_with("default-release") {
@@ -39,11 +47,11 @@ fn query_bookmarks_from_path(db_path: &Path) -> Result<Vec<WebBookmark>> {
     Ok(bookmarks)
 }
 
-pub fn get_firefox_bookmarks(cfg_path: &Path) -> Result<Vec<WebBookmark>> {
+pub fn get_firefox_bookmarks(cfg_path: &Path, profile: Option<&str>) -> Result<Vec<WebBookmark>> {
     let mut ff_path = cfg_path.to_path_buf();
     ff_path.push(FF_ROOT_PATH);
 
-    let mut db_path = with_default_firefox_profile(&ff_path)?;
+    let mut db_path = with_default_firefox_profile(&ff_path, profile)?;
     db_path.push(PLACES);
     query_bookmarks_from_path(&db_path)
 }
@@ -51,7 +59,7 @@ pub fn get_firefox_bookmarks(cfg_path: &Path) -> Result<Vec<WebBookmark>> {
 #[test]
 fn firefox_bookmarks() -> Result<()> {
     let bookmarks_path = dirs::home_dir().ok_or_else(|| anyhow!("no home dir"))?;
-    let bookmarks = get_firefox_bookmarks(&bookmarks_path)?;
+    let bookmarks = get_firefox_bookmarks(&bookmarks_path, None)?;
 
     for bkm in bookmarks {
         println!("{:?}", bkm);